@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, fmt::Write as _, path::Path};
+
 fn main() {
     if std::env::var("CARGO_CFG_TARGET_OS").unwrap() == "android" {
         let android_abi = match std::env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
@@ -14,4 +16,270 @@ fn main() {
             .join(format!("app/src/main/jniLibs/{android_abi}/lib"));
         println!("cargo:rustc-link-search={}", libdir.to_string_lossy());
     }
+
+    reflect_shader_bindings();
+}
+
+/// Bind group layouts shared between shaders: the module name to generate, and each shader
+/// (in `src/shaders/wgsl`) that uses it with the group index it's bound at.
+/// Names are taken from the first shader listed.
+const SHARED_GROUPS: &[(&str, &[(&str, u32)])] = &[
+    (
+        "scene",
+        &[
+            ("fullscreen.wgsl", 0),
+            ("debug_pattern.wgsl", 0),
+            ("mesh.wgsl", 0),
+            ("textured_quad.wgsl", 0),
+        ],
+    ),
+    (
+        "material",
+        &[("textured_quad.wgsl", 1), ("mesh.wgsl", 1), ("mipmap.wgsl", 0)],
+    ),
+];
+
+/// One `@group(g) @binding(b)` global, as it appears in one shader
+#[derive(Debug, Clone, PartialEq)]
+struct Binding {
+    name: String,
+    /// Rust expression for the `wgpu::BindingType`, with `has_dynamic_offset: false`
+    ty: String,
+    /// Struct members for buffers: name, byte offset and byte size
+    members: Vec<(String, u32, u32)>,
+    /// Byte size of the buffer's type, zero for textures and samplers
+    size: u32,
+    /// `wgpu::ShaderStages` bits of the entry points using it
+    stages: u32,
+}
+
+/// Parse and validate every shader, and write `$OUT_DIR/shader_bindings.rs` with a module per
+/// [SHARED_GROUPS] entry holding its `BindGroupLayoutEntry` list, binding indices, and buffer
+/// sizes and member offsets. Panics if shaders sharing a group disagree about it, or a shader
+/// declares a binding that isn't part of any shared group.
+fn reflect_shader_bindings() {
+    let shader_dir = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("src/shaders/wgsl");
+    println!("cargo:rerun-if-changed={}", shader_dir.display());
+
+    let mut shaders = BTreeMap::new();
+    for entry in std::fs::read_dir(&shader_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "wgsl") {
+            println!("cargo:rerun-if-changed={}", path.display());
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            shaders.insert(name, reflect_shader(&path));
+        }
+    }
+
+    // Every binding must belong to a shared group, or nothing creates a layout for it
+    for (shader, bindings) in shaders.iter() {
+        for &(group, binding) in bindings.keys() {
+            let covered = SHARED_GROUPS
+                .iter()
+                .any(|(_, users)| users.contains(&(shader.as_str(), group)));
+            assert!(
+                covered,
+                "{shader}: @group({group}) @binding({binding}) isn't in any of build.rs's SHARED_GROUPS"
+            );
+        }
+    }
+
+    let mut out = String::from("// Generated by build.rs from src/shaders/wgsl, do not edit\n");
+    for &(module, users) in SHARED_GROUPS {
+        // binding index -> (first shader to declare it, its reflection)
+        let mut merged: BTreeMap<u32, (&str, Binding)> = BTreeMap::new();
+        for &(shader, group) in users {
+            let bindings = shaders
+                .get(shader)
+                .unwrap_or_else(|| panic!("SHARED_GROUPS names {shader}, which isn't in {}", shader_dir.display()));
+            for (&(_, index), binding) in bindings.range((group, 0)..=(group, u32::MAX)) {
+                match merged.get_mut(&index) {
+                    None => {
+                        merged.insert(index, (shader, binding.clone()));
+                    }
+                    Some((first, existing)) => {
+                        assert!(
+                            existing.ty == binding.ty && existing.members == binding.members && existing.size == binding.size,
+                            "{module} binding {index}: {shader} @group({group}) declares {binding:?}, \
+                             but {first} declares {existing:?}"
+                        );
+                        existing.stages |= binding.stages;
+                    }
+                }
+            }
+        }
+        write_group_module(&mut out, module, users, &merged);
+    }
+
+    let out_path = Path::new(&std::env::var("OUT_DIR").unwrap()).join("shader_bindings.rs");
+    std::fs::write(out_path, out).unwrap();
+}
+
+/// The resource bindings of a shader, keyed by (group, binding)
+fn reflect_shader(path: &Path) -> BTreeMap<(u32, u32), Binding> {
+    let name = path.display().to_string();
+    let source = std::fs::read_to_string(path).unwrap();
+    let module = naga::front::wgsl::parse_str(&source)
+        .unwrap_or_else(|e| panic!("{}", e.emit_to_string_with_path(&source, &name)));
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::MULTIVIEW | naga::valid::Capabilities::PUSH_CONSTANT,
+    )
+    .validate(&module)
+    .unwrap_or_else(|e| panic!("{}", e.emit_to_string_with_path(&source, &name)));
+
+    let mut bindings = BTreeMap::new();
+    for (handle, global) in module.global_variables.iter() {
+        let Some(resource) = &global.binding else {
+            continue;
+        };
+        let var_name = global.name.clone().unwrap_or_else(|| format!("binding_{}", resource.binding));
+        let at = format!("{name}: {var_name}");
+
+        let stages = module
+            .entry_points
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| !info.get_entry_point(i)[handle].is_empty())
+            .map(|(_, entry_point)| match entry_point.stage {
+                naga::ShaderStage::Vertex => 1,
+                naga::ShaderStage::Fragment => 2,
+                naga::ShaderStage::Compute => 4,
+            })
+            .fold(0, |stages, stage| stages | stage);
+
+        let inner = &module.types[global.ty].inner;
+        let mut members = vec![];
+        let mut size = 0;
+        let ty = match global.space {
+            naga::AddressSpace::Uniform | naga::AddressSpace::Storage { .. } => {
+                size = inner.size(module.to_ctx());
+                if let naga::TypeInner::Struct { members: struct_members, .. } = inner {
+                    for member in struct_members {
+                        members.push((
+                            member.name.clone().unwrap_or_default(),
+                            member.offset,
+                            module.types[member.ty].inner.size(module.to_ctx()),
+                        ));
+                    }
+                }
+                let buffer_ty = match global.space {
+                    naga::AddressSpace::Storage { access } => format!(
+                        "wgpu::BufferBindingType::Storage {{ read_only: {} }}",
+                        !access.contains(naga::StorageAccess::STORE)
+                    ),
+                    _ => "wgpu::BufferBindingType::Uniform".to_string(),
+                };
+                format!(
+                    "wgpu::BindingType::Buffer {{ ty: {buffer_ty}, has_dynamic_offset: false, \
+                     min_binding_size: std::num::NonZeroU64::new({size}) }}"
+                )
+            }
+            naga::AddressSpace::Handle => match inner {
+                naga::TypeInner::Image { dim, arrayed, class } => image_binding_type(&at, *dim, *arrayed, class),
+                // Filtering is the common case, naga can't tell whether the shader needs it
+                naga::TypeInner::Sampler { comparison: false } => {
+                    "wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)".to_string()
+                }
+                naga::TypeInner::Sampler { comparison: true } => {
+                    "wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)".to_string()
+                }
+                other => panic!("{at}: unsupported handle type {other:?}"),
+            },
+            other => panic!("{at}: unsupported address space {other:?}"),
+        };
+
+        bindings.insert(
+            (resource.group, resource.binding),
+            Binding {
+                name: var_name,
+                ty,
+                members,
+                size,
+                stages,
+            },
+        );
+    }
+    bindings
+}
+
+fn image_binding_type(at: &str, dim: naga::ImageDimension, arrayed: bool, class: &naga::ImageClass) -> String {
+    let view_dimension = match (dim, arrayed) {
+        (naga::ImageDimension::D1, false) => "D1",
+        (naga::ImageDimension::D2, false) => "D2",
+        (naga::ImageDimension::D2, true) => "D2Array",
+        (naga::ImageDimension::D3, false) => "D3",
+        (naga::ImageDimension::Cube, false) => "Cube",
+        (naga::ImageDimension::Cube, true) => "CubeArray",
+        other => panic!("{at}: unsupported image dimension {other:?}"),
+    };
+    match class {
+        naga::ImageClass::Sampled { kind, multi } => {
+            let sample_type = match kind {
+                // Assume filterable, to match the Filtering samplers above. Multisampled textures never are.
+                naga::ScalarKind::Float => format!("wgpu::TextureSampleType::Float {{ filterable: {} }}", !multi),
+                naga::ScalarKind::Sint => "wgpu::TextureSampleType::Sint".to_string(),
+                naga::ScalarKind::Uint => "wgpu::TextureSampleType::Uint".to_string(),
+                other => panic!("{at}: unsupported texture sample kind {other:?}"),
+            };
+            format!(
+                "wgpu::BindingType::Texture {{ sample_type: {sample_type}, view_dimension: \
+                 wgpu::TextureViewDimension::{view_dimension}, multisampled: {multi} }}"
+            )
+        }
+        naga::ImageClass::Depth { multi } => format!(
+            "wgpu::BindingType::Texture {{ sample_type: wgpu::TextureSampleType::Depth, view_dimension: \
+             wgpu::TextureViewDimension::{view_dimension}, multisampled: {multi} }}"
+        ),
+        naga::ImageClass::Storage { .. } => panic!("{at}: storage textures aren't supported yet"),
+    }
+}
+
+fn write_group_module(out: &mut String, module: &str, users: &[(&str, u32)], bindings: &BTreeMap<u32, (&str, Binding)>) {
+    let used_by = users
+        .iter()
+        .map(|(shader, group)| format!("`{shader}` group {group}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(out, "\n/// Used by {used_by}").unwrap();
+    writeln!(out, "pub mod {module} {{").unwrap();
+
+    writeln!(
+        out,
+        "    /// Every binding with the stages that use it. Buffers have `has_dynamic_offset: false`."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    pub const LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; {}] = [",
+        bindings.len()
+    )
+    .unwrap();
+    for (index, (_, binding)) in bindings.iter() {
+        writeln!(
+            out,
+            "        wgpu::BindGroupLayoutEntry {{ binding: {index}, visibility: wgpu::ShaderStages::from_bits_truncate({}), ty: {}, count: None }},",
+            binding.stages, binding.ty
+        )
+        .unwrap();
+    }
+    writeln!(out, "    ];").unwrap();
+
+    for (index, (shader, binding)) in bindings.iter() {
+        writeln!(out, "\n    /// `{}` in `{shader}`", binding.name).unwrap();
+        writeln!(out, "    pub mod {} {{", binding.name).unwrap();
+        writeln!(out, "        pub const BINDING: u32 = {index};").unwrap();
+        if binding.size > 0 {
+            writeln!(out, "        /// Size of the whole buffer type, in bytes").unwrap();
+            writeln!(out, "        pub const SIZE: usize = {};", binding.size).unwrap();
+        }
+        for (member, offset, size) in binding.members.iter() {
+            let upper = member.to_uppercase();
+            writeln!(out, "        /// Byte offset of `{member}`, which is {size} bytes").unwrap();
+            writeln!(out, "        pub const {upper}: usize = {offset};").unwrap();
+            writeln!(out, "        pub const {upper}_SIZE: usize = {size};").unwrap();
+        }
+        writeln!(out, "    }}").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
 }
@@ -0,0 +1,188 @@
+//! A stack of action set layers, e.g. a menu layer over gameplay, which games set up in [crate::game::Game::init].
+//! [crate::App] syncs the active layers once per frame, before [crate::game::Game::tick_to].
+//!
+//! Where two active layers bind the same input, `XR_EXT_active_action_set_priority` gives it to the
+//! higher priority layer. Without the extension every active layer sees every input, so a layer can be
+//! made [ActionLayer::exclusive] to stop lower priority layers being synced at all while it's active.
+
+use anyhow::anyhow;
+
+use crate::shell::XrShell;
+use crate::xr;
+use crate::Result;
+
+pub struct ActionLayer {
+    pub name: &'static str,
+    pub action_set: xr::ActionSet,
+    /// Higher priorities take shared bindings from lower ones
+    pub priority: u32,
+    /// Only sync these subaction paths, e.g. just the left hand. All of them when empty.
+    pub subaction_paths: Vec<xr::Path>,
+    /// While active, don't sync any lower priority layer
+    pub exclusive: bool,
+    pub active: bool,
+}
+
+#[derive(Default)]
+pub struct ActionLayers {
+    /// Highest priority first
+    layers: Vec<ActionLayer>,
+}
+
+impl ActionLayers {
+    /// Add an active layer syncing every subaction path.
+    /// Its action set must also be attached to the session, see [crate::bindings::BindingRegistry].
+    pub fn push(&mut self, name: &'static str, action_set: &xr::ActionSet, priority: u32) -> &mut ActionLayer {
+        let index = insertion_index(self.layers.iter().map(|layer| layer.priority), priority);
+        self.layers.insert(index, ActionLayer {
+            name,
+            action_set: action_set.clone(),
+            priority,
+            subaction_paths: vec![],
+            exclusive: false,
+            active: true,
+        });
+        &mut self.layers[index]
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ActionLayer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    /// Takes effect at the next sync. Errors if there's no layer called `name`.
+    pub fn set_active(&mut self, name: &str, active: bool) -> Result<()> {
+        let layer = self.get_mut(name).ok_or_else(|| anyhow!("No action layer called {name:?}"))?;
+        layer.active = active;
+        Ok(())
+    }
+
+    pub fn is_active(&self, name: &str) -> bool {
+        self.layers.iter().any(|layer| layer.active && layer.name == name)
+    }
+
+    /// The layers to sync, highest priority first, stopping after the first exclusive one
+    fn synced_layers(&self) -> impl Iterator<Item = &ActionLayer> {
+        let synced = synced_mask(self.layers.iter().map(|layer| (layer.active, layer.exclusive)));
+        self.layers
+            .iter()
+            .zip(synced)
+            .filter_map(|(layer, synced)| synced.then_some(layer))
+    }
+
+    /// [xrSyncActions](https://registry.khronos.org/OpenXR/specs/1.0/man/html/xrSyncActions.html) for the active layers.
+    /// Does nothing if none are active.
+    pub fn sync(&self, xr_shell: &XrShell) -> Result<()> {
+        let mut active_action_sets = vec![];
+        let mut priorities = vec![];
+        for layer in self.synced_layers() {
+            if layer.subaction_paths.is_empty() {
+                active_action_sets.push(xr::sys::ActiveActionSet {
+                    action_set: layer.action_set.as_raw(),
+                    subaction_path: xr::Path::NULL,
+                });
+            } else {
+                active_action_sets.extend(layer.subaction_paths.iter().map(|&subaction_path| xr::sys::ActiveActionSet {
+                    action_set: layer.action_set.as_raw(),
+                    subaction_path,
+                }));
+            }
+            priorities.push(xr::sys::ActiveActionSetPriorityEXT {
+                action_set: layer.action_set.as_raw(),
+                priority_override: layer.priority,
+            });
+        }
+        if active_action_sets.is_empty() {
+            return Ok(());
+        }
+
+        let priorities = xr::sys::ActiveActionSetPrioritiesEXT {
+            ty: xr::sys::ActiveActionSetPrioritiesEXT::TYPE,
+            next: std::ptr::null(),
+            action_set_priority_count: priorities.len() as u32,
+            action_set_priorities: priorities.as_ptr(),
+        };
+        let priorities_enabled = xr_shell.xr_instance.exts().ext_active_action_set_priority.is_some();
+        let sync_info = xr::sys::ActionsSyncInfo {
+            ty: xr::sys::ActionsSyncInfo::TYPE,
+            next: if priorities_enabled {
+                &priorities as *const _ as *const std::ffi::c_void
+            } else {
+                std::ptr::null()
+            },
+            count_active_action_sets: active_action_sets.len() as u32,
+            active_action_sets: active_action_sets.as_ptr(),
+        };
+        // The openxr crate's sync_actions can't chain the priorities, so call it directly
+        let result = unsafe { (xr_shell.xr_instance.fp().sync_actions)(xr_shell.xr_session.as_raw(), &sync_info) };
+        // SESSION_LOSS_PENDING and SESSION_NOT_FOCUSED are successes, just with nothing to read
+        if result.into_raw() < 0 {
+            return Err(anyhow!("xrSyncActions failed: {result:?}"));
+        }
+        Ok(())
+    }
+}
+
+/// Where to insert a layer of `priority` into a stack with `priorities`, highest first.
+/// After any layers of the same priority, so the first pushed stays on top.
+fn insertion_index(priorities: impl IntoIterator<Item = u32>, priority: u32) -> usize {
+    priorities.into_iter().take_while(|&existing| existing >= priority).count()
+}
+
+/// Whether to sync each of a stack's layers, given as `(active, exclusive)` highest priority first:
+/// the active ones, down to and including the first active exclusive one
+fn synced_mask(layers: impl IntoIterator<Item = (bool, bool)>) -> Vec<bool> {
+    let mut below_exclusive = false;
+    layers
+        .into_iter()
+        .map(|(active, exclusive)| {
+            let synced = active && !below_exclusive;
+            below_exclusive |= synced && exclusive;
+            synced
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The priorities of a stack after pushing `pushed` in order, as (priority, push order)
+    fn stack(pushed: &[u32]) -> Vec<(u32, usize)> {
+        let mut stack: Vec<(u32, usize)> = vec![];
+        for (order, &priority) in pushed.iter().enumerate() {
+            let index = insertion_index(stack.iter().map(|&(priority, _)| priority), priority);
+            stack.insert(index, (priority, order));
+        }
+        stack
+    }
+
+    #[test]
+    fn layers_are_kept_in_priority_order() {
+        assert_eq!(stack(&[1, 5, 3]), [(5, 1), (3, 2), (1, 0)]);
+    }
+
+    #[test]
+    fn first_pushed_stays_on_top_of_equal_priorities() {
+        assert_eq!(stack(&[2, 2, 7, 2]), [(7, 2), (2, 0), (2, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn all_active_layers_are_synced() {
+        assert_eq!(synced_mask([(true, false), (true, false)]), [true, true]);
+    }
+
+    #[test]
+    fn inactive_layers_are_skipped() {
+        assert_eq!(synced_mask([(true, false), (false, false), (true, false)]), [true, false, true]);
+    }
+
+    #[test]
+    fn exclusive_layer_cuts_off_layers_below() {
+        assert_eq!(synced_mask([(true, false), (true, true), (true, false), (true, true)]), [true, true, false, false]);
+    }
+
+    #[test]
+    fn inactive_exclusive_layer_cuts_off_nothing() {
+        assert_eq!(synced_mask([(false, true), (true, false)]), [false, true]);
+    }
+}
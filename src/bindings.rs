@@ -0,0 +1,486 @@
+//! Combining the bindings of several [Controls] schemes.
+//!
+//! [xrSuggestInteractionProfileBindings](https://registry.khronos.org/OpenXR/specs/1.0/man/html/xrSuggestInteractionProfileBindings.html)
+//! replaces any previous suggestion for the same interaction profile, and
+//! [xrAttachSessionActionSets](https://registry.khronos.org/OpenXR/specs/1.0/man/html/xrAttachSessionActionSets.html)
+//! can only be called once per session, so every scheme's bindings and action sets have to be gathered
+//! before either is called.
+//!
+//! The actions and bindings themselves can be declared in a [BindingsFile].
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, ensure, Context};
+
+use crate::controls::Controls;
+use crate::shell::XrShell;
+use crate::xr;
+use crate::Result;
+
+/// Suggested bindings merged per interaction profile, and the action sets they belong to
+#[derive(Default)]
+pub struct BindingRegistry<'a> {
+    action_sets: Vec<&'a xr::ActionSet>,
+    /// In the order each profile was first suggested
+    profiles: Vec<(&'a str, Vec<xr::Binding<'a>>)>,
+}
+
+impl<'a> BindingRegistry<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a scheme's action set and suggested bindings
+    pub fn add(&mut self, xr_instance: &xr::Instance, controls: &'a impl Controls) -> Result<&mut Self> {
+        self.action_sets.push(controls.action_set());
+        for (profile, bindings) in controls.suggested_bindings(xr_instance)? {
+            self.add_bindings(profile, bindings);
+        }
+        Ok(self)
+    }
+
+    /// Add bindings for one interaction profile, after any already added for it
+    pub fn add_bindings(&mut self, profile: &'a str, bindings: Vec<xr::Binding<'a>>) {
+        match self.profiles.iter_mut().find(|(existing, _)| *existing == profile) {
+            Some((_, existing)) => existing.extend(bindings),
+            None => self.profiles.push((profile, bindings)),
+        }
+    }
+
+    /// Suggest each profile's bindings once, then attach every action set to the session.
+    /// Consumes the registry, since neither can be done again.
+    pub fn suggest_and_attach(self, xr_shell: &XrShell) -> Result<()> {
+        for (profile, bindings) in &self.profiles {
+            log::info!("Suggesting {} bindings for {profile}", bindings.len());
+            xr_shell
+                .xr_instance
+                .suggest_interaction_profile_bindings(xr_shell.xr_instance.string_to_path(profile)?, bindings)
+                .with_context(|| format!("Suggesting bindings for {profile}"))?;
+        }
+        xr_shell.xr_session.attach_action_sets(&self.action_sets)?;
+        Ok(())
+    }
+}
+
+/// Action sets, their actions and their bindings in each interaction profile, declared in a
+/// [RON](https://github.com/ron-rs/ron) file so a new controller can be supported without changing code.
+/// See `src/bindings/point_and_click.ron`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BindingsFile {
+    pub action_sets: Vec<ActionSetDecl>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ActionSetDecl {
+    pub name: String,
+    pub localized_name: String,
+    /// Passed to xrCreateActionSet; see also [crate::action_layers::ActionLayer::priority]
+    #[serde(default)]
+    pub priority: u32,
+    pub actions: Vec<ActionDecl>,
+    pub profiles: Vec<ProfileDecl>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ActionDecl {
+    pub name: String,
+    pub localized_name: String,
+    pub kind: ActionKind,
+    /// Top level user paths like `/user/hand/left`, which the action can be read for separately
+    #[serde(default)]
+    pub subaction_paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum ActionKind {
+    Bool,
+    Float,
+    Vector2,
+    Pose,
+    Haptic,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ProfileDecl {
+    /// e.g. `/interaction_profiles/oculus/touch_controller`
+    pub profile: String,
+    pub bindings: Vec<BindingDecl>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BindingDecl {
+    /// The name of an action in the same action set
+    pub action: String,
+    /// e.g. `/user/hand/left/input/trigger/value`
+    pub path: String,
+}
+
+/// Whether `path` is well formed according to
+/// [the spec](https://registry.khronos.org/OpenXR/specs/1.0/html/xrspec.html#well-formed-path-strings):
+/// lowercase ASCII letters, digits, `-`, `_` and `.` in `/` separated components, without an empty or
+/// all-`.` component.
+fn check_path_syntax(path: &str) -> Result<()> {
+    let Some(components) = path.strip_prefix('/') else {
+        bail!("Path {path:?} doesn't start with '/'");
+    };
+    for component in components.split('/') {
+        if component.is_empty() || component.chars().all(|c| c == '.') {
+            bail!("Path {path:?} has an empty or all-'.' component");
+        }
+        if let Some(c) = component
+            .chars()
+            .find(|&c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || "-_.".contains(c)))
+        {
+            bail!("Path {path:?} contains {c:?}, only lowercase ASCII letters, digits, '-', '_' and '.' are allowed");
+        }
+    }
+    Ok(())
+}
+
+impl BindingsFile {
+    /// Parse and [validate](BindingsFile::validate) a file's contents. `origin` names it in errors.
+    pub fn parse(source: &str, origin: &str) -> Result<Self> {
+        let file: Self = ron::from_str(source).with_context(|| format!("Parsing bindings {origin}"))?;
+        file.validate().with_context(|| format!("Invalid bindings in {origin}"))?;
+        Ok(file)
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path).with_context(|| format!("Reading bindings {}", path.display()))?;
+        Self::parse(&source, &path.display().to_string())
+    }
+
+    /// Everything that can be checked without the runtime, with errors naming the offending path.
+    /// Whether each binding path exists in its interaction profile is left to the runtime, which
+    /// rejects the whole profile in [BindingRegistry::suggest_and_attach].
+    pub fn validate(&self) -> Result<()> {
+        let mut set_names = HashSet::new();
+        for set in &self.action_sets {
+            if !set_names.insert(&set.name) {
+                bail!("Action set {:?} is declared twice", set.name);
+            }
+            set.validate().with_context(|| format!("In action set {:?}", set.name))?;
+        }
+        Ok(())
+    }
+
+    pub fn action_set(&self, name: &str) -> Result<&ActionSetDecl> {
+        self.action_sets
+            .iter()
+            .find(|set| set.name == name)
+            .with_context(|| format!("No action set called {name:?}"))
+    }
+}
+
+impl ActionSetDecl {
+    fn validate(&self) -> Result<()> {
+        let mut actions = HashMap::new();
+        for action in &self.actions {
+            if actions.insert(action.name.as_str(), action).is_some() {
+                bail!("Action {:?} is declared twice", action.name);
+            }
+            for subaction_path in &action.subaction_paths {
+                check_path_syntax(subaction_path)
+                    .and_then(|()| {
+                        ensure!(
+                            subaction_path.starts_with("/user/") && subaction_path.matches('/').count() <= 3,
+                            "Subaction path {subaction_path:?} isn't a top level user path like /user/hand/left"
+                        );
+                        Ok(())
+                    })
+                    .with_context(|| format!("In action {:?}", action.name))?;
+            }
+        }
+
+        let mut profiles = HashSet::new();
+        for profile in &self.profiles {
+            if !profiles.insert(&profile.profile) {
+                bail!("Interaction profile {:?} is declared twice", profile.profile);
+            }
+            check_path_syntax(&profile.profile)
+                .and_then(|()| {
+                    ensure!(
+                        profile.profile.starts_with("/interaction_profiles/"),
+                        "Interaction profile {:?} isn't under /interaction_profiles/",
+                        profile.profile
+                    );
+                    Ok(())
+                })?;
+            for binding in &profile.bindings {
+                actions
+                    .get(binding.action.as_str())
+                    .with_context(|| format!("Binding path {:?} is for undeclared action {:?}", binding.path, binding.action))
+                    .and_then(|action| binding.validate(action))
+                    .with_context(|| format!("In interaction profile {:?}", profile.profile))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the action set and its actions, named for the runtime's debug messages
+    pub fn create(&self, xr_shell: &XrShell) -> Result<DeclaredActionSet> {
+        let instance = &xr_shell.xr_instance;
+        let path = |path: &str| instance.string_to_path(path).with_context(|| format!("Path {path:?}"));
+
+        let action_set = instance.create_action_set(&self.name, &self.localized_name, self.priority)?;
+        xr_shell.set_xr_object_name(xr::sys::ObjectType::ACTION_SET, action_set.as_raw(), &self.name);
+
+        let mut actions = HashMap::new();
+        for decl in &self.actions {
+            let subaction_paths = decl.subaction_paths.iter().map(|p| path(p)).collect::<Result<Vec<_>>>()?;
+            let (name, localized_name) = (decl.name.as_str(), decl.localized_name.as_str());
+            let action = match decl.kind {
+                ActionKind::Bool => DeclaredAction::Bool(action_set.create_action(name, localized_name, &subaction_paths)?),
+                ActionKind::Float => DeclaredAction::Float(action_set.create_action(name, localized_name, &subaction_paths)?),
+                ActionKind::Vector2 => DeclaredAction::Vector2(action_set.create_action(name, localized_name, &subaction_paths)?),
+                ActionKind::Pose => DeclaredAction::Pose(action_set.create_action(name, localized_name, &subaction_paths)?),
+                ActionKind::Haptic => DeclaredAction::Haptic(action_set.create_action(name, localized_name, &subaction_paths)?),
+            };
+            xr_shell.set_xr_object_name(xr::sys::ObjectType::ACTION, action.as_raw(), &format!("{}/{name}", self.name));
+            actions.insert(decl.name.clone(), action);
+        }
+
+        let mut profiles = vec![];
+        for profile in &self.profiles {
+            let bindings = profile
+                .bindings
+                .iter()
+                .map(|binding| Ok((binding.action.clone(), path(&binding.path)?)))
+                .collect::<Result<Vec<_>>>()?;
+            profiles.push((profile.profile.clone(), bindings));
+        }
+
+        Ok(DeclaredActionSet {
+            name: self.name.clone(),
+            priority: self.priority,
+            action_set,
+            actions,
+            profiles,
+        })
+    }
+}
+
+impl BindingDecl {
+    fn validate(&self, action: &ActionDecl) -> Result<()> {
+        check_path_syntax(&self.path)?;
+        // A subaction path only reads bindings under it, anything else would never be seen
+        if !action.subaction_paths.is_empty()
+            && !action
+                .subaction_paths
+                .iter()
+                .any(|subaction_path| self.path.starts_with(&format!("{subaction_path}/")))
+        {
+            bail!(
+                "Binding path {:?} isn't under any of action {:?}'s subaction paths {:?}",
+                self.path,
+                action.name,
+                action.subaction_paths
+            );
+        }
+        let output = self.path.contains("/output/");
+        if output != (action.kind == ActionKind::Haptic) {
+            bail!(
+                "Binding path {:?} is an {}, which can't be bound to {:?} action {:?}",
+                self.path,
+                if output { "output" } else { "input" },
+                action.kind,
+                action.name
+            );
+        }
+        Ok(())
+    }
+}
+
+/// An action created from an [ActionDecl]
+pub enum DeclaredAction {
+    Bool(xr::Action<bool>),
+    Float(xr::Action<f32>),
+    Vector2(xr::Action<xr::Vector2f>),
+    Pose(xr::Action<xr::Posef>),
+    Haptic(xr::Action<xr::Haptic>),
+}
+
+impl DeclaredAction {
+    fn as_raw(&self) -> xr::sys::Action {
+        match self {
+            Self::Bool(action) => action.as_raw(),
+            Self::Float(action) => action.as_raw(),
+            Self::Vector2(action) => action.as_raw(),
+            Self::Pose(action) => action.as_raw(),
+            Self::Haptic(action) => action.as_raw(),
+        }
+    }
+
+    fn kind(&self) -> ActionKind {
+        match self {
+            Self::Bool(_) => ActionKind::Bool,
+            Self::Float(_) => ActionKind::Float,
+            Self::Vector2(_) => ActionKind::Vector2,
+            Self::Pose(_) => ActionKind::Pose,
+            Self::Haptic(_) => ActionKind::Haptic,
+        }
+    }
+}
+
+/// The action types a [DeclaredAction] can hold
+pub trait DeclaredActionType: xr::ActionTy {
+    const KIND: ActionKind;
+    /// The typed action, or `action` back if it's another type
+    fn unwrap(action: DeclaredAction) -> std::result::Result<xr::Action<Self>, DeclaredAction>;
+}
+
+macro_rules! declared_action_type {
+    ($ty:ty, $variant:ident) => {
+        impl DeclaredActionType for $ty {
+            const KIND: ActionKind = ActionKind::$variant;
+            fn unwrap(action: DeclaredAction) -> std::result::Result<xr::Action<Self>, DeclaredAction> {
+                match action {
+                    DeclaredAction::$variant(action) => Ok(action),
+                    other => Err(other),
+                }
+            }
+        }
+    };
+}
+declared_action_type!(bool, Bool);
+declared_action_type!(f32, Float);
+declared_action_type!(xr::Vector2f, Vector2);
+declared_action_type!(xr::Posef, Pose);
+declared_action_type!(xr::Haptic, Haptic);
+
+/// An action set and actions created from an [ActionSetDecl], with its bindings resolved to paths.
+/// A control scheme [takes](DeclaredActionSet::take) the typed actions it reads, then
+/// [finishes](DeclaredActionSet::finish) to get the action set and bindings.
+pub struct DeclaredActionSet {
+    pub name: String,
+    pub priority: u32,
+    action_set: xr::ActionSet,
+    actions: HashMap<String, DeclaredAction>,
+    /// (interaction profile, [(action name, binding path)])
+    profiles: Vec<(String, Vec<(String, xr::Path)>)>,
+}
+
+impl DeclaredActionSet {
+    /// Take the action called `name`, which must have been declared with type `T`
+    pub fn take<T: DeclaredActionType>(&mut self, name: &str) -> Result<xr::Action<T>> {
+        let action = self
+            .actions
+            .remove(name)
+            .with_context(|| format!("Action set {:?} has no action {name:?}", self.name))?;
+        let kind = action.kind();
+        T::unwrap(action).map_err(|_| {
+            anyhow::anyhow!("Action {:?} in action set {:?} is declared as {kind:?}, but should be {:?}", name, self.name, T::KIND)
+        })
+    }
+
+    /// The action set, and each profile's (interaction profile, [(action name, binding path)]).
+    /// Every declared action must have been taken, since nothing would read the rest.
+    pub fn finish(self) -> Result<(xr::ActionSet, Vec<(String, Vec<(String, xr::Path)>)>)> {
+        if !self.actions.is_empty() {
+            let mut unused: Vec<_> = self.actions.keys().collect();
+            unused.sort();
+            bail!("Action set {:?} declares actions which nothing reads: {unused:?}", self.name);
+        }
+        Ok((self.action_set, self.profiles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One action set with a click action and a menu action, and `bindings` for the simple controller
+    fn file(extra_actions: &str, bindings: &str) -> String {
+        format!(
+            r#"(
+                action_sets: [(
+                    name: "test",
+                    localized_name: "Test",
+                    actions: [
+                        (name: "click", localized_name: "Click", kind: Bool, subaction_paths: ["/user/hand/left"]),
+                        (name: "haptic", localized_name: "Haptic", kind: Haptic, subaction_paths: ["/user/hand/left"]),
+                        {extra_actions}
+                    ],
+                    profiles: [(
+                        profile: "/interaction_profiles/khr/simple_controller",
+                        bindings: [{bindings}],
+                    )],
+                )],
+            )"#
+        )
+    }
+
+    fn parse_error(source: &str) -> String {
+        format!("{:#}", BindingsFile::parse(source, "test.ron").unwrap_err())
+    }
+
+    #[test]
+    fn valid_file_parses() {
+        let file = BindingsFile::parse(
+            &file(
+                "",
+                r#"(action: "click", path: "/user/hand/left/input/select/click"),
+                   (action: "haptic", path: "/user/hand/left/output/haptic"),"#,
+            ),
+            "test.ron",
+        )
+        .unwrap();
+        assert_eq!(file.action_set("test").unwrap().profiles[0].bindings.len(), 2);
+    }
+
+    #[test]
+    fn built_in_bindings_are_valid() {
+        BindingsFile::parse(crate::controls::PointAndClickControls::DEFAULT_BINDINGS, "point_and_click.ron").unwrap();
+    }
+
+    #[test]
+    fn bad_path_is_named() {
+        let error = parse_error(&file("", r#"(action: "click", path: "/user/hand/left/input/Select/click"),"#));
+        assert!(error.contains("test.ron"), "{error}");
+        assert!(error.contains("/user/hand/left/input/Select/click"), "{error}");
+        assert!(error.contains("'S'"), "{error}");
+
+        let error = parse_error(&file("", r#"(action: "click", path: "/user/hand/left//select/click"),"#));
+        assert!(error.contains("/user/hand/left//select/click"), "{error}");
+    }
+
+    #[test]
+    fn binding_outside_subaction_paths_is_named() {
+        let error = parse_error(&file("", r#"(action: "click", path: "/user/hand/right/input/select/click"),"#));
+        assert!(error.contains("/user/hand/right/input/select/click"), "{error}");
+        assert!(error.contains("\"click\""), "{error}");
+    }
+
+    #[test]
+    fn input_bound_to_haptic_is_named() {
+        let error = parse_error(&file("", r#"(action: "haptic", path: "/user/hand/left/input/select/click"),"#));
+        assert!(error.contains("/user/hand/left/input/select/click"), "{error}");
+        assert!(error.contains("\"haptic\""), "{error}");
+    }
+
+    #[test]
+    fn unknown_action_is_named() {
+        let error = parse_error(&file("", r#"(action: "jump", path: "/user/hand/left/input/select/click"),"#));
+        assert!(error.contains("\"jump\""), "{error}");
+        assert!(error.contains("/user/hand/left/input/select/click"), "{error}");
+        assert!(error.contains("/interaction_profiles/khr/simple_controller"), "{error}");
+    }
+
+    #[test]
+    fn duplicate_action_is_named() {
+        let error = parse_error(&file(r#"(name: "click", localized_name: "Click again", kind: Bool),"#, ""));
+        assert!(error.contains("\"click\" is declared twice"), "{error}");
+        assert!(error.contains("\"test\""), "{error}");
+    }
+
+    #[test]
+    fn bad_subaction_path_is_named() {
+        let error = parse_error(&file(
+            r#"(name: "grip", localized_name: "Grip", kind: Pose, subaction_paths: ["/user/hand/left/input"]),"#,
+            "",
+        ));
+        assert!(error.contains("/user/hand/left/input"), "{error}");
+        assert!(error.contains("\"grip\""), "{error}");
+    }
+}
@@ -0,0 +1,156 @@
+//! GPU buffers which are rewritten every frame.
+//!
+//! The CPU writes the data for frame N while the GPU may still be reading frame N-1's,
+//! so each buffer is split into [XrShell::FRAMES_IN_FLIGHT] slots and frame N only touches
+//! slot [XrShell::frame_slot]. Slots are selected at draw time with dynamic offsets,
+//! so one bind group covers every slot.
+
+use std::{marker::PhantomData, num::NonZeroU64};
+
+use anyhow::Result;
+
+use crate::shell::XrShell;
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+/// Minimum dynamic offset alignment for buffers with the given usage
+fn offset_alignment(xr_shell: &XrShell, usage: wgpu::BufferUsages) -> u64 {
+    let limits = xr_shell.wgpu_device.limits();
+    if usage.contains(wgpu::BufferUsages::STORAGE) {
+        limits.min_storage_buffer_offset_alignment as u64
+    } else {
+        limits.min_uniform_buffer_offset_alignment as u64
+    }
+}
+
+/// One `T` per frame in flight.
+///
+/// Bind with [UniformRing::binding] into a layout entry with `has_dynamic_offset: true`,
+/// and pass [UniformRing::dynamic_offset] when setting the bind group.
+pub struct UniformRing<T: bytemuck::Pod> {
+    buffer: wgpu::Buffer,
+    stride: u64,
+    _t: PhantomData<T>,
+}
+impl<T: bytemuck::Pod> UniformRing<T> {
+    const _CHECK_SIZE: () = assert!(std::mem::size_of::<T>() > 0);
+
+    /// `usage` should be [wgpu::BufferUsages::UNIFORM] or [wgpu::BufferUsages::STORAGE]
+    pub fn new(xr_shell: &XrShell, usage: wgpu::BufferUsages, label: Option<&str>) -> Self {
+        let stride = align_up(std::mem::size_of::<T>() as u64, offset_alignment(xr_shell, usage));
+        let buffer = xr_shell.wgpu_device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: stride * XrShell::FRAMES_IN_FLIGHT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | usage,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            stride,
+            _t: PhantomData,
+        }
+    }
+
+    /// Write the value for the current frame
+    pub fn write(&self, xr_shell: &XrShell, value: &T) {
+        xr_shell.wgpu_queue.write_buffer(
+            &self.buffer,
+            self.dynamic_offset(xr_shell) as u64,
+            bytemuck::bytes_of(value),
+        );
+    }
+
+    /// Offset of the current frame's slot
+    pub fn dynamic_offset(&self, xr_shell: &XrShell) -> u32 {
+        (self.stride * xr_shell.frame_slot() as u64) as u32
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.buffer,
+            offset: 0,
+            size: NonZeroU64::new(std::mem::size_of::<T>() as u64),
+        })
+    }
+}
+
+/// Per-frame linear allocator for per-object data of type `T`.
+///
+/// Every frame, [FrameArena::push] each object's data to get its dynamic offset, then
+/// [FrameArena::flush] before submitting. The whole frame is uploaded with a single write,
+/// and every object shares the same bind group.
+pub struct FrameArena<T: bytemuck::Pod> {
+    buffer: wgpu::Buffer,
+    stride: u64,
+    capacity: u64,
+    /// CPU-side copy of the current frame's slot
+    staging: Vec<u8>,
+    _t: PhantomData<T>,
+}
+impl<T: bytemuck::Pod> FrameArena<T> {
+    const _CHECK_SIZE: () = assert!(std::mem::size_of::<T>() > 0);
+
+    /// Create an arena holding up to `capacity` values per frame.
+    /// `usage` should be [wgpu::BufferUsages::UNIFORM] or [wgpu::BufferUsages::STORAGE].
+    pub fn new(xr_shell: &XrShell, capacity: u64, usage: wgpu::BufferUsages, label: Option<&str>) -> Self {
+        let stride = align_up(std::mem::size_of::<T>() as u64, offset_alignment(xr_shell, usage));
+        let buffer = xr_shell.wgpu_device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: stride * capacity * XrShell::FRAMES_IN_FLIGHT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | usage,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            stride,
+            capacity,
+            staging: Vec::with_capacity((stride * capacity) as usize),
+            _t: PhantomData,
+        }
+    }
+
+    /// Number of values pushed this frame
+    pub fn len(&self) -> u64 {
+        self.staging.len() as u64 / self.stride
+    }
+
+    /// Discard everything pushed so far, ready for a new frame
+    pub fn clear(&mut self) {
+        self.staging.clear();
+    }
+
+    /// Add a value for the current frame, returning the dynamic offset to bind it with
+    pub fn push(&mut self, xr_shell: &XrShell, value: &T) -> Result<u32> {
+        if self.len() >= self.capacity {
+            anyhow::bail!("FrameArena is full ({} values per frame)", self.capacity);
+        }
+        let offset_in_slot = self.staging.len() as u64;
+        self.staging.extend_from_slice(bytemuck::bytes_of(value));
+        self.staging.resize((offset_in_slot + self.stride) as usize, 0);
+        Ok((self.slot_offset(xr_shell) + offset_in_slot) as u32)
+    }
+
+    /// Upload everything pushed this frame and [FrameArena::clear]
+    pub fn flush(&mut self, xr_shell: &XrShell) {
+        if !self.staging.is_empty() {
+            xr_shell
+                .wgpu_queue
+                .write_buffer(&self.buffer, self.slot_offset(xr_shell), &self.staging);
+        }
+        self.clear();
+    }
+
+    fn slot_offset(&self, xr_shell: &XrShell) -> u64 {
+        self.stride * self.capacity * xr_shell.frame_slot() as u64
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.buffer,
+            offset: 0,
+            size: NonZeroU64::new(std::mem::size_of::<T>() as u64),
+        })
+    }
+}
@@ -1,7 +1,10 @@
 //! Module with various structures for capturing and querying different forms of input
 //! e.g. simple controllers, oculus/touch_controller, hands
 
-use crate::math::Pose;
+use anyhow::Context;
+
+use crate::bindings::ActionSetDecl;
+use crate::math::{Pose, Vec3};
 use crate::shell::XrShell;
 use crate::xr;
 use crate::Result;
@@ -25,7 +28,8 @@ pub trait Controls {
     /// 
     /// Return the suggested_bindings for (potentially multiple) (interaction_profile, [(binding -> action)]),
     /// where each action exposed in [Controls::action_set] has a binding in each interaction profile.
-    /// These may be combined with bindings for other control schemes before being passed to 
+    /// These may be combined with bindings for other control schemes, with a [crate::bindings::BindingRegistry],
+    /// before being passed to
     /// [xrSuggestInteractionProfileBindings](https://registry.khronos.org/OpenXR/specs/1.0/man/html/xrSuggestInteractionProfileBindings.html),
     /// which "the application can call \[...\] **once** per interaction profile".
     /// 
@@ -35,28 +39,483 @@ pub trait Controls {
         Vec<xr::Binding<'_>>
     )>>;
 
+    /// Poses and velocities are relative to `space`
     fn locate(&self, xr_shell: &XrShell, space: &xr::Space, time: xr::Time) -> Result<Self::InputInfo>;
     fn apply(&self, xr_shell: &XrShell, output: &Self::OutputInfo) -> Result<()>;
 }
 
+/// How a normalized analog value is mapped after its dead zone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    /// `value.powf(exponent)`, e.g. 2 for finer control near rest and 0.5 for a quicker response
+    Power(f32),
+}
+impl ResponseCurve {
+    /// Map 0..=1 onto 0..=1
+    pub fn apply(&self, value: f32) -> f32 {
+        match *self {
+            ResponseCurve::Linear => value,
+            ResponseCurve::Power(exponent) => value.powf(exponent),
+        }
+    }
+}
+
+/// Shaping for a 0 to 1 axis like a trigger or squeeze
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisConfig {
+    /// Values at or below this read as 0, to hide noise and resting fingers
+    pub dead_zone: f32,
+    /// Values at or above this read as 1, since worn hardware may never quite reach it
+    pub saturation: f32,
+    pub curve: ResponseCurve,
+}
+impl Default for AxisConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.05,
+            saturation: 0.95,
+            curve: ResponseCurve::Linear,
+        }
+    }
+}
+impl AxisConfig {
+    pub fn apply(&self, raw: f32) -> f32 {
+        let raw = raw.clamp(0.0, 1.0);
+        if raw <= self.dead_zone {
+            return 0.0;
+        }
+        let range = (self.saturation - self.dead_zone).max(f32::EPSILON);
+        self.curve.apply(((raw - self.dead_zone) / range).min(1.0))
+    }
+}
+
+/// Shaping for a thumbstick. The dead zone is radial, so small movements off-axis aren't lost,
+/// and the direction is kept while the curve is applied to the distance from center.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickConfig {
+    pub dead_zone: f32,
+    pub saturation: f32,
+    pub curve: ResponseCurve,
+}
+impl Default for StickConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            saturation: 0.95,
+            curve: ResponseCurve::Linear,
+        }
+    }
+}
+impl StickConfig {
+    pub fn apply(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let magnitude = x.hypot(y);
+        if magnitude <= self.dead_zone {
+            return [0.0, 0.0];
+        }
+        let range = (self.saturation - self.dead_zone).max(f32::EPSILON);
+        let shaped = self.curve.apply(((magnitude - self.dead_zone) / range).min(1.0));
+        [x / magnitude * shaped, y / magnitude * shaped]
+    }
+}
+
+/// Turns an analog value into a button, with hysteresis so it doesn't chatter around one threshold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    /// Pressed once the value reaches this
+    pub press: f32,
+    /// Released once the value drops below this, which should be below [Threshold::press]
+    pub release: f32,
+}
+impl Default for Threshold {
+    fn default() -> Self {
+        Self {
+            press: 0.75,
+            release: 0.65,
+        }
+    }
+}
+impl Threshold {
+    /// Whether the button is pressed now, given whether it was last time
+    pub fn apply(&self, value: f32, was_pressed: bool) -> bool {
+        if was_pressed {
+            value >= self.release
+        } else {
+            value >= self.press
+        }
+    }
+}
+
+/// The shaping [PointAndClickControls] and [OculusTouchControllers] apply to their analog actions
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AnalogConfig {
+    pub trigger: AxisConfig,
+    pub squeeze: AxisConfig,
+    pub thumbstick: StickConfig,
+    /// When the raw trigger value counts as a button, for controllers without a trigger click
+    pub trigger_button: Threshold,
+    /// When the raw squeeze value counts as a button, e.g. for grabbing
+    pub squeeze_button: Threshold,
+}
+
+/// One boolean action as of the last [xrSyncActions](https://registry.khronos.org/OpenXR/specs/1.0/man/html/xrSyncActions.html).
+/// The edges are only meaningful if the scheme is located once per sync.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ButtonState {
+    pub held: bool,
+    /// Went down since the last sync
+    pub pressed: bool,
+    /// Went up since the last sync
+    pub released: bool,
+    /// How long it's been held, 0 while released
+    pub held_secs: f32,
+    /// Has just been held for [ButtonTiming::long_press_secs], true for one sync per press
+    pub long_press: bool,
+    /// Pressed within [ButtonTiming::double_click_secs] of the previous press.
+    /// The press after a double click starts a new pair rather than making a triple.
+    pub double_click: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ButtonTiming {
+    pub long_press_secs: f32,
+    pub double_click_secs: f32,
+}
+impl Default for ButtonTiming {
+    fn default() -> Self {
+        Self {
+            long_press_secs: 0.5,
+            double_click_secs: 0.3,
+        }
+    }
+}
+
+/// What [ButtonStates] remembers about one button between syncs
+#[derive(Debug, Clone, Copy, Default)]
+struct ButtonHistory {
+    /// Time of the last press which could start a double click
+    last_press: Option<xr::Time>,
+    long_press_reported: bool,
+    /// For analog actions read as buttons, which have no boolean state of their own
+    analog_held: bool,
+    analog_change_time: Option<xr::Time>,
+}
+
+/// Turns raw boolean action states into [ButtonState]s, remembering what's needed for long presses
+/// and double clicks. Buttons are told apart by action name and subaction path.
+#[derive(Default)]
+pub struct ButtonStates {
+    pub timing: ButtonTiming,
+    // Interior mutability because [Controls::locate] takes &self
+    histories: std::cell::RefCell<std::collections::HashMap<(&'static str, u64), ButtonHistory>>,
+}
+impl ButtonStates {
+    /// Read a boolean action, treating an inactive action as released
+    pub fn read(
+        &self,
+        xr_shell: &XrShell,
+        name: &'static str,
+        action: &xr::Action<bool>,
+        subpath: xr::Path,
+        time: xr::Time,
+    ) -> Result<ButtonState> {
+        let state = action.state(&xr_shell.xr_session, subpath)?;
+        Ok(self.update(name, subpath, &state, time))
+    }
+
+    /// Like [ButtonStates::update], with the boolean state made up from the raw value and `threshold`
+    pub fn update_analog(
+        &self,
+        name: &'static str,
+        subpath: xr::Path,
+        state: &xr::ActionState<f32>,
+        threshold: &Threshold,
+        time: xr::Time,
+    ) -> ButtonState {
+        let button_state = {
+            let mut histories = self.histories.borrow_mut();
+            let history = histories.entry((name, subpath.into_raw())).or_default();
+            let was_held = history.analog_held;
+            let held = state.is_active && threshold.apply(state.current_state, was_held);
+            let changed = held != was_held;
+            if changed {
+                // The value last changed when it crossed the threshold, or close enough
+                history.analog_change_time = Some(state.last_change_time);
+            }
+            history.analog_held = held;
+            xr::ActionState {
+                current_state: held,
+                changed_since_last_sync: changed,
+                last_change_time: history.analog_change_time.unwrap_or(state.last_change_time),
+                is_active: true,
+            }
+        };
+        self.update(name, subpath, &button_state, time)
+    }
+
+    pub fn update(
+        &self,
+        name: &'static str,
+        subpath: xr::Path,
+        state: &xr::ActionState<bool>,
+        time: xr::Time,
+    ) -> ButtonState {
+        let held = state.is_active && state.current_state;
+        let changed = state.is_active && state.changed_since_last_sync;
+        let secs_between = |from: xr::Time, to: xr::Time| (to.as_nanos() - from.as_nanos()).max(0) as f32 / 1e9;
+
+        let mut histories = self.histories.borrow_mut();
+        let history = histories.entry((name, subpath.into_raw())).or_default();
+
+        let pressed = changed && held;
+        let held_secs = if held { secs_between(state.last_change_time, time) } else { 0.0 };
+        // Between the presses themselves, so it doesn't depend on frame timing or display latency
+        let double_click = pressed
+            && history.last_press.is_some_and(|last_press| {
+                secs_between(last_press, state.last_change_time) <= self.timing.double_click_secs
+            });
+        if pressed {
+            history.last_press = if double_click { None } else { Some(state.last_change_time) };
+            history.long_press_reported = false;
+        }
+        let long_press = held && !history.long_press_reported && held_secs >= self.timing.long_press_secs;
+        if long_press {
+            history.long_press_reported = true;
+        }
+
+        ButtonState {
+            held,
+            pressed,
+            released: changed && !held,
+            held_secs,
+            long_press,
+            double_click,
+        }
+    }
+}
+
+/// Which parts of a located pose the runtime could provide, from [xr::SpaceLocationFlags]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackingFlags {
+    pub position_valid: bool,
+    pub orientation_valid: bool,
+    /// Measured rather than inferred, e.g. a 3DoF controller's position from a neck/arm model isn't tracked
+    pub position_tracked: bool,
+    pub orientation_tracked: bool,
+}
+impl From<xr::SpaceLocationFlags> for TrackingFlags {
+    fn from(flags: xr::SpaceLocationFlags) -> Self {
+        Self {
+            position_valid: flags.contains(xr::SpaceLocationFlags::POSITION_VALID),
+            orientation_valid: flags.contains(xr::SpaceLocationFlags::ORIENTATION_VALID),
+            position_tracked: flags.contains(xr::SpaceLocationFlags::POSITION_TRACKED),
+            orientation_tracked: flags.contains(xr::SpaceLocationFlags::ORIENTATION_TRACKED),
+        }
+    }
+}
+impl TrackingFlags {
+    pub fn is_valid(&self) -> bool {
+        self.position_valid && self.orientation_valid
+    }
+    pub fn is_tracked(&self) -> bool {
+        self.position_tracked && self.orientation_tracked
+    }
+}
+
+/// How a located space is moving, from [xr::SpaceVelocity] and relative to the space it was located in.
+/// Either part is `None` when the runtime can't provide it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Velocity {
+    /// Metres per second
+    pub linear: Option<Vec3>,
+    /// Axis scaled by radians per second
+    pub angular: Option<Vec3>,
+}
+impl From<&xr::SpaceVelocity> for Velocity {
+    fn from(velocity: &xr::SpaceVelocity) -> Self {
+        let flags = velocity.velocity_flags;
+        Self {
+            linear: flags
+                .contains(xr::SpaceVelocityFlags::LINEAR_VALID)
+                .then(|| velocity.linear_velocity.into()),
+            angular: flags
+                .contains(xr::SpaceVelocityFlags::ANGULAR_VALID)
+                .then(|| velocity.angular_velocity.into()),
+        }
+    }
+}
+
+/// What to report for a pose whose position or orientation the runtime says is invalid.
+/// Only the invalid half is replaced, so a controller that has lost position keeps its live orientation.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum InvalidPosePolicy {
+    /// Report the hand as missing
+    #[default]
+    Hide,
+    /// Keep reporting the last valid pose
+    HoldLast,
+    /// Carry the last valid pose along at the velocity it was moving, for at most `max_secs`,
+    /// then hide the hand
+    Extrapolate { max_secs: f32 },
+}
+
+/// The last valid pose of one space, and how it was moving then
+#[derive(Debug, Clone, Copy)]
+struct GoodPose {
+    pose: Pose,
+    time: xr::Time,
+    linear_velocity: cgmath::Vector3<f32>,
+    /// Axis scaled by radians per second
+    angular_velocity: cgmath::Vector3<f32>,
+}
+impl GoodPose {
+    fn extrapolate(&self, secs: f32) -> Pose {
+        use cgmath::{InnerSpace, Rotation3};
+        let position: cgmath::Vector3<f32> = self.pose.position.into();
+        let orientation: cgmath::Quaternion<f32> = self.pose.orientation.into();
+        let speed = self.angular_velocity.magnitude();
+        let orientation = if speed > 1e-6 {
+            cgmath::Quaternion::from_axis_angle(self.angular_velocity / speed, cgmath::Rad(speed * secs)) * orientation
+        } else {
+            orientation
+        };
+        Pose {
+            position: (position + self.linear_velocity * secs).into(),
+            orientation: orientation.into(),
+        }
+    }
+}
+
+/// Velocities taking `from` to `to` over `secs`, for runtimes that don't report them
+fn estimate_velocities(from: Pose, to: Pose, secs: f32) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+    use cgmath::InnerSpace;
+    let from_position: cgmath::Vector3<f32> = from.position.into();
+    let to_position: cgmath::Vector3<f32> = to.position.into();
+    let from_orientation: cgmath::Quaternion<f32> = from.orientation.into();
+    let to_orientation: cgmath::Quaternion<f32> = to.orientation.into();
+
+    let mut delta = to_orientation * from_orientation.conjugate();
+    // Take the short way round
+    if delta.s < 0.0 {
+        delta = -delta;
+    }
+    let sin_half_angle = delta.v.magnitude();
+    let angular_velocity = if sin_half_angle > 1e-6 {
+        let angle = 2.0 * sin_half_angle.atan2(delta.s);
+        delta.v / sin_half_angle * (angle / secs)
+    } else {
+        cgmath::Vector3::new(0.0, 0.0, 0.0)
+    };
+    ((to_position - from_position) / secs, angular_velocity)
+}
+
+/// Applies an [InvalidPosePolicy] to located spaces, remembering each one's last valid pose.
+/// Spaces are told apart by name and subaction path, like [ButtonStates].
+#[derive(Default)]
+pub struct PoseStates {
+    pub policy: InvalidPosePolicy,
+    // Interior mutability because [Controls::locate] takes &self
+    last_good: std::cell::RefCell<std::collections::HashMap<(&'static str, u64), GoodPose>>,
+}
+impl PoseStates {
+    /// The pose to report for `location`, or `None` if the hand should be hidden.
+    /// `velocity` is what [InvalidPosePolicy::Extrapolate] extrapolates with, where it's valid.
+    pub fn resolve(
+        &self,
+        name: &'static str,
+        subpath: xr::Path,
+        location: &xr::SpaceLocation,
+        velocity: &xr::SpaceVelocity,
+        time: xr::Time,
+    ) -> Option<Pose> {
+        let flags = TrackingFlags::from(location.location_flags);
+        let located: Pose = location.pose.into();
+        let secs_since = |then: xr::Time| (time.as_nanos() - then.as_nanos()) as f32 / 1e9;
+
+        let mut last_good = self.last_good.borrow_mut();
+        let key = (name, subpath.into_raw());
+        if flags.is_valid() {
+            let (estimated_linear, estimated_angular) = match last_good.get(&key) {
+                Some(last) if secs_since(last.time) > 0.0 => {
+                    estimate_velocities(last.pose, located, secs_since(last.time))
+                }
+                Some(last) => (last.linear_velocity, last.angular_velocity),
+                None => (cgmath::Vector3::new(0.0, 0.0, 0.0), cgmath::Vector3::new(0.0, 0.0, 0.0)),
+            };
+            let velocity = Velocity::from(velocity);
+            let linear_velocity = velocity.linear.map_or(estimated_linear, Into::into);
+            let angular_velocity = velocity.angular.map_or(estimated_angular, Into::into);
+            last_good.insert(key, GoodPose {
+                pose: located,
+                time,
+                linear_velocity,
+                angular_velocity,
+            });
+            return Some(located);
+        }
+
+        // Without a valid pose yet there's nothing to hold or extrapolate from
+        let last = last_good.get(&key)?;
+        let fallback = match self.policy {
+            InvalidPosePolicy::Hide => return None,
+            InvalidPosePolicy::HoldLast => last.pose,
+            InvalidPosePolicy::Extrapolate { max_secs } => {
+                let secs = secs_since(last.time);
+                if secs > max_secs {
+                    return None;
+                }
+                last.extrapolate(secs)
+            }
+        };
+        Some(Pose {
+            position: if flags.position_valid { located.position } else { fallback.position },
+            orientation: if flags.orientation_valid { located.orientation } else { fallback.orientation },
+        })
+    }
+
+    /// Drop the remembered poses for a subaction path, e.g. when its controller goes inactive,
+    /// so a stale pose isn't held or extrapolated when it comes back
+    pub fn forget(&self, subpath: xr::Path) {
+        let subpath = subpath.into_raw();
+        self.last_good.borrow_mut().retain(|&(_, key_subpath), _| key_subpath != subpath);
+    }
+}
+
 pub struct PointAndClickHand {
+    /// Resolved according to [PoseStates::policy]
     pub grip: Pose,
+    pub grip_tracking: TrackingFlags,
+    /// Resolved according to [PoseStates::policy]
     pub point: Pose,
-    pub click: bool,
+    pub point_tracking: TrackingFlags,
+    pub grip_velocity: Velocity,
+    pub point_velocity: Velocity,
+    pub click: ButtonState,
+    /// Shaped by [AnalogConfig::trigger]. Just 0 or 1 on controllers with a select button instead.
+    pub trigger: f32,
+    /// Shaped by [AnalogConfig::squeeze], 0 on controllers without a grip button
+    pub squeeze: f32,
+    /// The raw squeeze through [AnalogConfig::squeeze_button]
+    pub squeeze_button: ButtonState,
+    /// Shaped by [AnalogConfig::thumbstick]: +X right and +Y forward, zero on controllers without one
+    pub thumbstick: [f32; 2],
 }
 
 pub struct PointAndClickInput {
     pub lh: Option<PointAndClickHand>,
     pub rh: Option<PointAndClickHand>,
-    pub menu_button: bool,
+    pub menu_button: ButtonState,
 }
 
 pub struct PointAndClickControls {
     lh_subpath: xr::Path,
     rh_subpath: xr::Path,
-    
+
+    /// Declared in a [crate::bindings::BindingsFile], see [PointAndClickControls::DEFAULT_BINDINGS]
     action_set: xr::ActionSet,
-    
+    /// (interaction profile, [(action, binding path)]) from the same file
+    bindings: Vec<(String, Vec<(PointAndClickAction, xr::Path)>)>,
+
     grip: xr::Action<xr::Posef>,
     lh_grip_space: xr::Space,
     rh_grip_space: xr::Space,
@@ -67,43 +526,85 @@ pub struct PointAndClickControls {
 
     click: xr::Action<bool>,
     menu_button: xr::Action<bool>,
+    haptic: xr::Action<xr::Haptic>,
+
+    trigger: xr::Action<f32>,
+    squeeze: xr::Action<f32>,
+    thumbstick: xr::Action<xr::Vector2f>,
 
+    /// Applied to the analog actions in [Controls::locate]
+    pub analog: AnalogConfig,
+    pub buttons: ButtonStates,
+    pub poses: PoseStates,
+}
+
+/// The actions [PointAndClickControls] reads, which its bindings are resolved to when it's created
+#[derive(Debug, Clone, Copy)]
+enum PointAndClickAction {
+    Grip,
+    Point,
+    Click,
+    MenuButton,
+    Haptic,
+    Trigger,
+    Squeeze,
+    Thumbstick,
+}
+impl PointAndClickAction {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "grip" => Self::Grip,
+            "point" => Self::Point,
+            "click" => Self::Click,
+            "menu_button" => Self::MenuButton,
+            "haptic" => Self::Haptic,
+            "trigger" => Self::Trigger,
+            "squeeze" => Self::Squeeze,
+            "thumbstick" => Self::Thumbstick,
+            _ => return None,
+        })
+    }
 }
+
 impl PointAndClickControls {
-    pub fn new(xr_shell: &XrShell, action_set_name: &'static str, localized_name: &'static str) -> Result<Self> {
-        // Create an action set to encapsulate our actions
-        // TODO: What is the unicode compatibility for OpenXR? Can we use UTF-8 &str everywhere?
-        let action_set =
-            xr_shell.xr_instance.create_action_set(action_set_name, localized_name, 0)?;
+    /// Actions and bindings for the common controllers, built in so they're there on Android
+    pub const DEFAULT_BINDINGS: &'static str = include_str!("bindings/point_and_click.ron");
 
+    /// Create the actions declared by `decl`, which must include these, with a subaction path for each hand
+    /// on all but `menu_button`:
+    /// - `grip` and `point`: Pose, the palm and pointing orientations
+    /// - `click` and `menu_button`: Bool
+    /// - `trigger` and `squeeze`: Float
+    /// - `thumbstick`: Vector2
+    /// - `haptic`: Haptic
+    pub fn new(xr_shell: &XrShell, decl: &ActionSetDecl) -> Result<Self> {
         let lh_subpath = xr_shell.xr_instance.string_to_path("/user/hand/left")?;
         let rh_subpath = xr_shell.xr_instance.string_to_path("/user/hand/right")?;
 
-        // TODO localisation /shrug
-        // We have four categories of input:
-        // - the palm or "grip" orientation for each hand separately
-        // - the pointing orientation for each hand separately
-        // - the "click" input for each hand separately
-        // - the "menu button" click input, we don't care which hand.
-        // The inputs for each separate hand are registered as a single action for both with "subaction_paths" for each hand.
-        // This means theoretically the inputs will be presented nicely on platforms which expose rebinding controls like Steam VR.
-        let grip =
-            action_set.create_action::<xr::Posef>("grip", "Palm Orientation", &[
-                lh_subpath,
-                rh_subpath,
-            ])?;
-        let point = 
-            action_set.create_action::<xr::Posef>("point", "Pointing Direction", &[
-                lh_subpath,
-                rh_subpath,
-            ])?;
-        let click = 
-            action_set.create_action::<bool>("click", "Click", &[
-                lh_subpath,
-                rh_subpath,
-            ])?;
-        let menu_button = 
-            action_set.create_action::<bool>("menu_button", "Menu Button", &[])?;
+        let mut actions = decl.create(xr_shell)?;
+        let grip = actions.take::<xr::Posef>("grip")?;
+        let point = actions.take::<xr::Posef>("point")?;
+        let click = actions.take::<bool>("click")?;
+        let menu_button = actions.take::<bool>("menu_button")?;
+        let haptic = actions.take::<xr::Haptic>("haptic")?;
+        let trigger = actions.take::<f32>("trigger")?;
+        let squeeze = actions.take::<f32>("squeeze")?;
+        let thumbstick = actions.take::<xr::Vector2f>("thumbstick")?;
+        let (action_set, profiles) = actions.finish()?;
+        let bindings = profiles
+            .into_iter()
+            .map(|(profile, bindings)| {
+                let bindings = bindings
+                    .into_iter()
+                    .map(|(action, path)| {
+                        let action = PointAndClickAction::from_name(&action)
+                            .with_context(|| format!("{profile} binds unknown action {action:?}"))?;
+                        Ok((action, path))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((profile, bindings))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         // Create an action space for each device we want to locate
         let lh_grip_space = grip.create_space(
@@ -128,12 +629,23 @@ impl PointAndClickControls {
             xr::Posef::IDENTITY,
         )?;
 
+        // Name the spaces for the runtime's debug messages, the actions were named when created
+        for (space, name) in [
+            (&lh_grip_space, "left grip"),
+            (&rh_grip_space, "right grip"),
+            (&lh_point_space, "left point"),
+            (&rh_point_space, "right point"),
+        ] {
+            xr_shell.set_xr_object_name(xr::sys::ObjectType::SPACE, space.as_raw(), &format!("{} {name}", decl.name));
+        }
+
         Ok(Self {
             lh_subpath,
             rh_subpath,
 
             action_set,
-            
+            bindings,
+
             grip,
             lh_grip_space,
             rh_grip_space,
@@ -143,146 +655,1068 @@ impl PointAndClickControls {
             rh_point_space,
 
             click,
-            menu_button
+            menu_button,
+            haptic,
+
+            trigger,
+            squeeze,
+            thumbstick,
+
+            analog: AnalogConfig::default(),
+            buttons: ButtonStates::default(),
+            poses: PoseStates::default(),
         })
     }
 }
 impl Controls for PointAndClickControls {
     type InputInfo = PointAndClickInput;
     
-    type OutputInfo = ();
+    type OutputInfo = HapticOutput;
 
     fn action_set(&self) -> &xr::ActionSet {
         &self.action_set
     }
-    fn suggested_bindings(&self, xr_instance: &xr::Instance) -> Result<Vec<(
+    fn suggested_bindings(&self, _xr_instance: &xr::Instance) -> Result<Vec<(
         &str,
         Vec<xr::Binding<'_>>
     )>> {
-        Ok(vec![
-            (
-                "/interaction_profiles/khr/simple_controller",
-                vec![
-                    xr::Binding::new(
-                        &self.grip,
-                        xr_instance.string_to_path("/user/hand/left/input/grip/pose")?
-                    ),
-                    xr::Binding::new(
-                        &self.grip,
-                        xr_instance.string_to_path("/user/hand/right/input/grip/pose")?
-                    ),
-
-                    xr::Binding::new(
-                        &self.point,
-                        xr_instance.string_to_path("/user/hand/left/input/aim/pose")?
-                    ),
-                    xr::Binding::new(
-                        &self.point,
-                        xr_instance.string_to_path("/user/hand/right/input/aim/pose")?
-                    ),
-
-                    xr::Binding::new(
-                        &self.click,
-                        xr_instance.string_to_path("/user/hand/left/input/select/click")?
-                    ),
-                    xr::Binding::new(
-                        &self.click,
-                        xr_instance.string_to_path("/user/hand/right/input/select/click")?
-                    ),
-
-                    xr::Binding::new(
-                        &self.menu_button,
-                        xr_instance.string_to_path("/user/hand/left/input/menu/click")?
-                    ),
-                    xr::Binding::new(
-                        &self.menu_button,
-                        xr_instance.string_to_path("/user/hand/right/input/menu/click")?
-                    ),
-                ]
-            )
-        ])
+        Ok(self
+            .bindings
+            .iter()
+            .map(|(profile, bindings)| {
+                let bindings = bindings
+                    .iter()
+                    .map(|&(action, path)| match action {
+                        PointAndClickAction::Grip => xr::Binding::new(&self.grip, path),
+                        PointAndClickAction::Point => xr::Binding::new(&self.point, path),
+                        PointAndClickAction::Click => xr::Binding::new(&self.click, path),
+                        PointAndClickAction::MenuButton => xr::Binding::new(&self.menu_button, path),
+                        PointAndClickAction::Haptic => xr::Binding::new(&self.haptic, path),
+                        PointAndClickAction::Trigger => xr::Binding::new(&self.trigger, path),
+                        PointAndClickAction::Squeeze => xr::Binding::new(&self.squeeze, path),
+                        PointAndClickAction::Thumbstick => xr::Binding::new(&self.thumbstick, path),
+                    })
+                    .collect();
+                (profile.as_str(), bindings)
+            })
+            .collect())
     }
     
     fn locate(&self, xr_shell: &XrShell, space: &xr::Space, time: xr::Time) -> Result<Self::InputInfo> {
-        // Find where our controllers are located in the Stage space
-        let lh_grip = self
+        // Find where our controllers are located in the given (tracking) space
+        let (lh_grip, lh_grip_velocity) = self
             .lh_grip_space
-            .locate(space, time)?;
-        let lh_point = self
+            .relate(space, time)?;
+        let (lh_point, lh_point_velocity) = self
             .lh_point_space
-            .locate(space, time)?;
+            .relate(space, time)?;
 
         let lh_active = 
-            self
-                .grip
+            self.grip
                 .is_active(&xr_shell.xr_session, self.lh_subpath)?
             &&
-            self
-                .point
+            self.point
                 .is_active(&xr_shell.xr_session, self.lh_subpath)?;
 
-        let rh_grip = self
+        let (rh_grip, rh_grip_velocity) = self
             .rh_grip_space
-            .locate(space, time)?;
-        let rh_point = self
+            .relate(space, time)?;
+        let (rh_point, rh_point_velocity) = self
             .rh_point_space
-            .locate(space, time)?;
+            .relate(space, time)?;
             
         let rh_active = 
-            self
-                .grip
+            self.grip
                 .is_active(&xr_shell.xr_session, self.rh_subpath)?
             &&
-            self
-                .point
+            self.point
                 .is_active(&xr_shell.xr_session, self.rh_subpath)?;
 
-        let lh_click = self.click.state(&xr_shell.xr_session, self.lh_subpath)?;
-        let rh_click = self.click.state(&xr_shell.xr_session, self.rh_subpath)?;
+        let lh_click = self.buttons.read(xr_shell, "click", &self.click, self.lh_subpath, time)?;
+        let rh_click = self.buttons.read(xr_shell, "click", &self.click, self.rh_subpath, time)?;
+
+        let analog = |subpath: xr::Path| -> Result<(f32, f32, ButtonState, [f32; 2])> {
+            let trigger = self.trigger.state(&xr_shell.xr_session, subpath)?.current_state;
+            let squeeze = self.squeeze.state(&xr_shell.xr_session, subpath)?;
+            let thumbstick = self.thumbstick.state(&xr_shell.xr_session, subpath)?.current_state;
+            Ok((
+                self.analog.trigger.apply(trigger),
+                self.analog.squeeze.apply(squeeze.current_state),
+                self.buttons.update_analog("squeeze", subpath, &squeeze, &self.analog.squeeze_button, time),
+                self.analog.thumbstick.apply([thumbstick.x, thumbstick.y]),
+            ))
+        };
+        let (lh_trigger, lh_squeeze, lh_squeeze_button, lh_thumbstick) = analog(self.lh_subpath)?;
+        let (rh_trigger, rh_squeeze, rh_squeeze_button, rh_thumbstick) = analog(self.rh_subpath)?;
 
-        let menu_click = self.menu_button.state(&xr_shell.xr_session, xr::Path::NULL)?;
+        let menu_click = self.buttons.read(xr_shell, "menu_button", &self.menu_button, xr::Path::NULL, time)?;
 
-        // TODO look at the flags for {lh,rh}_{grip,point}
+        let resolve = |subpath: xr::Path,
+                       active: bool,
+                       (grip, grip_velocity): (&xr::SpaceLocation, &xr::SpaceVelocity),
+                       (point, point_velocity): (&xr::SpaceLocation, &xr::SpaceVelocity)| {
+            if !active {
+                self.poses.forget(subpath);
+                return None;
+            }
+            // Resolve both before combining them, so each keeps its history up to date
+            let grip = self.poses.resolve("grip", subpath, grip, grip_velocity, time);
+            let point = self.poses.resolve("point", subpath, point, point_velocity, time);
+            grip.zip(point)
+        };
+        let lh_poses = resolve(self.lh_subpath, lh_active, (&lh_grip, &lh_grip_velocity), (&lh_point, &lh_point_velocity));
+        let rh_poses = resolve(self.rh_subpath, rh_active, (&rh_grip, &rh_grip_velocity), (&rh_point, &rh_point_velocity));
 
         Ok(PointAndClickInput {
-            lh: if lh_active {
+            lh: if let Some((grip, point)) = lh_poses {
                 Some(PointAndClickHand {
-                    grip: lh_grip.pose.into(),
-                    point: lh_point.pose.into(),
-                    click: lh_click.current_state
+                    grip,
+                    grip_tracking: lh_grip.location_flags.into(),
+                    grip_velocity: (&lh_grip_velocity).into(),
+                    point,
+                    point_tracking: lh_point.location_flags.into(),
+                    point_velocity: (&lh_point_velocity).into(),
+                    click: lh_click,
+                    trigger: lh_trigger,
+                    squeeze: lh_squeeze,
+                    squeeze_button: lh_squeeze_button,
+                    thumbstick: lh_thumbstick,
                 })
             } else {
                 None
             },
-            rh: if rh_active {
+            rh: if let Some((grip, point)) = rh_poses {
                 Some(PointAndClickHand {
-                    grip: rh_grip.pose.into(),
-                    point: rh_point.pose.into(),
-                    click: rh_click.current_state
+                    grip,
+                    grip_tracking: rh_grip.location_flags.into(),
+                    grip_velocity: (&rh_grip_velocity).into(),
+                    point,
+                    point_tracking: rh_point.location_flags.into(),
+                    point_velocity: (&rh_point_velocity).into(),
+                    click: rh_click,
+                    trigger: rh_trigger,
+                    squeeze: rh_squeeze,
+                    squeeze_button: rh_squeeze_button,
+                    thumbstick: rh_thumbstick,
                 })
             } else {
                 None
             },
-            menu_button: menu_click.is_active && menu_click.current_state
+            menu_button: menu_click,
         })
     }
     
-    fn apply(&self, _xr_shell: &XrShell, _output: &Self::OutputInfo) -> Result<()> {
-        Ok(())    
+    fn apply(&self, xr_shell: &XrShell, output: &Self::OutputInfo) -> Result<()> {
+        apply_haptic(xr_shell, &self.haptic, self.lh_subpath, &output.lh)?;
+        apply_haptic(xr_shell, &self.haptic, self.rh_subpath, &output.rh)
     }
 
     
 }
 
+
+/// One vibration, see [xr::HapticVibration]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vibration {
+    /// 0 to 1
+    pub amplitude: f32,
+    /// Hz, or `None` for whatever the runtime thinks is best
+    pub frequency: Option<f32>,
+    /// `None` for the shortest pulse the device supports
+    pub duration: Option<std::time::Duration>,
+}
+impl Vibration {
+    /// A short, strong pulse e.g. to confirm a click
+    pub const CLICK: Self = Self {
+        amplitude: 0.6,
+        frequency: None,
+        duration: None,
+    };
+}
+
+/// What to do with one hand's haptics this frame
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HapticCommand {
+    /// Leave any vibration running
+    #[default]
+    None,
+    /// Start a vibration, replacing any already running
+    Vibrate(Vibration),
+    /// Stop any vibration early
+    Stop,
+}
+
+/// Haptic output for both hands, for [Controls::apply]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HapticOutput {
+    pub lh: HapticCommand,
+    pub rh: HapticCommand,
+}
+
+/// Send one hand's [HapticCommand] to a haptic action
+fn apply_haptic(
+    xr_shell: &XrShell,
+    action: &xr::Action<xr::Haptic>,
+    subpath: xr::Path,
+    command: &HapticCommand,
+) -> Result<()> {
+    match command {
+        HapticCommand::None => {}
+        HapticCommand::Vibrate(vibration) => {
+            let duration = match vibration.duration {
+                Some(duration) => xr::Duration::from_nanos(duration.as_nanos().min(i64::MAX as u128) as i64),
+                None => xr::Duration::MIN_HAPTIC,
+            };
+            action.apply_feedback(
+                &xr_shell.xr_session,
+                subpath,
+                &xr::HapticVibration::new()
+                    .amplitude(vibration.amplitude.clamp(0.0, 1.0))
+                    .frequency(vibration.frequency.unwrap_or(xr::FREQUENCY_UNSPECIFIED))
+                    .duration(duration),
+            )?;
+        }
+        HapticCommand::Stop => action.stop_feedback(&xr_shell.xr_session, subpath)?,
+    }
+    Ok(())
+}
+
+/// A hand's subaction path and its grip and aim spaces
+struct HandSpaces {
+    subpath: xr::Path,
+    grip_space: xr::Space,
+    aim_space: xr::Space,
+}
+
+/// Where a hand's grip and aim are, as located by [HandActions::locate_hand]
+struct LocatedHand {
+    grip: Pose,
+    grip_tracking: TrackingFlags,
+    grip_velocity: Velocity,
+    aim: Pose,
+    aim_tracking: TrackingFlags,
+    aim_velocity: Velocity,
+}
+
+/// The action set, per-hand grip and aim poses and haptics which the fixed-profile schemes
+/// ([SimpleControllers] and [OculusTouchControllers]) share. Schemes create their other actions
+/// in [HandActions::action_set].
+struct HandActions {
+    action_set: xr::ActionSet,
+    grip: xr::Action<xr::Posef>,
+    aim: xr::Action<xr::Posef>,
+    haptic: xr::Action<xr::Haptic>,
+    lh: HandSpaces,
+    rh: HandSpaces,
+}
+impl HandActions {
+    fn new(xr_shell: &XrShell, action_set_name: &'static str, localized_name: &'static str) -> Result<Self> {
+        let action_set =
+            xr_shell.xr_instance.create_action_set(action_set_name, localized_name, 0)?;
+        xr_shell.set_xr_object_name(xr::sys::ObjectType::ACTION_SET, action_set.as_raw(), action_set_name);
+
+        let lh_subpath = xr_shell.xr_instance.string_to_path("/user/hand/left")?;
+        let rh_subpath = xr_shell.xr_instance.string_to_path("/user/hand/right")?;
+        let both_hands = [lh_subpath, rh_subpath];
+
+        let grip = action_set.create_action::<xr::Posef>("grip", "Palm Orientation", &both_hands)?;
+        let aim = action_set.create_action::<xr::Posef>("aim", "Pointing Direction", &both_hands)?;
+        let haptic = action_set.create_action::<xr::Haptic>("haptic", "Vibration", &both_hands)?;
+
+        let hand_spaces = |subpath: xr::Path, name: &str| -> Result<HandSpaces> {
+            let grip_space = grip.create_space(xr_shell.xr_session.clone(), subpath, xr::Posef::IDENTITY)?;
+            let aim_space = aim.create_space(xr_shell.xr_session.clone(), subpath, xr::Posef::IDENTITY)?;
+            xr_shell.set_xr_object_name(xr::sys::ObjectType::SPACE, grip_space.as_raw(), &format!("{action_set_name} {name} grip"));
+            xr_shell.set_xr_object_name(xr::sys::ObjectType::SPACE, aim_space.as_raw(), &format!("{action_set_name} {name} aim"));
+            Ok(HandSpaces {
+                subpath,
+                grip_space,
+                aim_space,
+            })
+        };
+        let lh = hand_spaces(lh_subpath, "left")?;
+        let rh = hand_spaces(rh_subpath, "right")?;
+
+        Ok(Self {
+            action_set,
+            grip,
+            aim,
+            haptic,
+            lh,
+            rh,
+        })
+    }
+
+    /// Subaction paths for actions which are read per hand
+    fn both_hands(&self) -> [xr::Path; 2] {
+        [self.lh.subpath, self.rh.subpath]
+    }
+
+    /// Grip, aim and haptic bindings for `hand`, "left" or "right", which every controller profile has
+    fn bindings(&self, xr_instance: &xr::Instance, hand: &str) -> Result<[xr::Binding<'_>; 3]> {
+        let path = |path: &str| xr_instance.string_to_path(path);
+        Ok([
+            xr::Binding::new(&self.grip, path(&format!("/user/hand/{hand}/input/grip/pose"))?),
+            xr::Binding::new(&self.aim, path(&format!("/user/hand/{hand}/input/aim/pose"))?),
+            xr::Binding::new(&self.haptic, path(&format!("/user/hand/{hand}/output/haptic"))?),
+        ])
+    }
+
+    /// `None` if the hand isn't active, or [PoseStates::policy] has no pose for it
+    fn locate_hand(
+        &self,
+        xr_shell: &XrShell,
+        poses: &PoseStates,
+        hand: &HandSpaces,
+        space: &xr::Space,
+        time: xr::Time,
+    ) -> Result<Option<LocatedHand>> {
+        let session = &xr_shell.xr_session;
+        let active = self.grip.is_active(session, hand.subpath)? && self.aim.is_active(session, hand.subpath)?;
+        if !active {
+            poses.forget(hand.subpath);
+            return Ok(None);
+        }
+
+        let (grip, grip_velocity) = hand.grip_space.relate(space, time)?;
+        let (aim, aim_velocity) = hand.aim_space.relate(space, time)?;
+        let (Some(grip_pose), Some(aim_pose)) = (
+            poses.resolve("grip", hand.subpath, &grip, &grip_velocity, time),
+            poses.resolve("aim", hand.subpath, &aim, &aim_velocity, time),
+        ) else {
+            return Ok(None);
+        };
+
+        Ok(Some(LocatedHand {
+            grip: grip_pose,
+            grip_tracking: grip.location_flags.into(),
+            grip_velocity: (&grip_velocity).into(),
+            aim: aim_pose,
+            aim_tracking: aim.location_flags.into(),
+            aim_velocity: (&aim_velocity).into(),
+        }))
+    }
+
+    fn apply_haptics(&self, xr_shell: &XrShell, output: &HapticOutput) -> Result<()> {
+        apply_haptic(xr_shell, &self.haptic, self.lh.subpath, &output.lh)?;
+        apply_haptic(xr_shell, &self.haptic, self.rh.subpath, &output.rh)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SimpleHand {
+    /// Resolved according to [PoseStates::policy]
+    pub grip: Pose,
+    pub grip_tracking: TrackingFlags,
+    /// Resolved according to [PoseStates::policy]
+    pub aim: Pose,
+    pub aim_tracking: TrackingFlags,
+    pub grip_velocity: Velocity,
+    pub aim_velocity: Velocity,
+    pub select: ButtonState,
+}
+
+pub struct SimpleInput {
+    pub lh: Option<SimpleHand>,
+    pub rh: Option<SimpleHand>,
+    /// Either hand's menu button
+    pub menu_button: ButtonState,
+}
+
 /// Simple controllers
-/// 
+///
 /// "/interaction_profiles/khr/simple_controller"
-struct SimpleControllers();
+///
+/// Every runtime has to support binding this profile to whatever controllers it has,
+/// so this works everywhere as the lowest common denominator: a pose, select, menu and haptics per hand.
+pub struct SimpleControllers {
+    hands: HandActions,
+
+    select: xr::Action<bool>,
+    menu_button: xr::Action<bool>,
+
+    pub buttons: ButtonStates,
+    pub poses: PoseStates,
+}
+impl SimpleControllers {
+    pub const INTERACTION_PROFILE: &'static str = "/interaction_profiles/khr/simple_controller";
+
+    pub fn new(xr_shell: &XrShell, action_set_name: &'static str, localized_name: &'static str) -> Result<Self> {
+        let hands = HandActions::new(xr_shell, action_set_name, localized_name)?;
+        let both_hands = hands.both_hands();
+
+        let select = hands.action_set.create_action::<bool>("select", "Select", &both_hands)?;
+        let menu_button = hands.action_set.create_action::<bool>("menu_button", "Menu Button", &[])?;
+
+        Ok(Self {
+            hands,
+            select,
+            menu_button,
+            buttons: ButtonStates::default(),
+            poses: PoseStates::default(),
+        })
+    }
+
+    fn locate_hand(
+        &self,
+        xr_shell: &XrShell,
+        hand: &HandSpaces,
+        space: &xr::Space,
+        time: xr::Time,
+    ) -> Result<Option<SimpleHand>> {
+        let Some(located) = self.hands.locate_hand(xr_shell, &self.poses, hand, space, time)? else {
+            return Ok(None);
+        };
+        Ok(Some(SimpleHand {
+            grip: located.grip,
+            grip_tracking: located.grip_tracking,
+            aim: located.aim,
+            aim_tracking: located.aim_tracking,
+            grip_velocity: located.grip_velocity,
+            aim_velocity: located.aim_velocity,
+            select: self.buttons.read(xr_shell, "select", &self.select, hand.subpath, time)?,
+        }))
+    }
+}
+impl Controls for SimpleControllers {
+    type InputInfo = SimpleInput;
 
-/// Oculus/Meta Quest 3 controllers
-/// 
-/// "/interaction_profiles/oculus/touch_controller"?
+    type OutputInfo = HapticOutput;
+
+    fn action_set(&self) -> &xr::ActionSet {
+        &self.hands.action_set
+    }
+
+    fn suggested_bindings(&self, xr_instance: &xr::Instance) -> Result<Vec<(
+        &str,
+        Vec<xr::Binding<'_>>
+    )>> {
+        let path = |path: &str| xr_instance.string_to_path(path);
+        let mut bindings = vec![];
+        for hand in ["left", "right"] {
+            bindings.extend(self.hands.bindings(xr_instance, hand)?);
+            bindings.extend([
+                xr::Binding::new(&self.select, path(&format!("/user/hand/{hand}/input/select/click"))?),
+                xr::Binding::new(&self.menu_button, path(&format!("/user/hand/{hand}/input/menu/click"))?),
+            ]);
+        }
+        Ok(vec![(Self::INTERACTION_PROFILE, bindings)])
+    }
+
+    fn locate(&self, xr_shell: &XrShell, space: &xr::Space, time: xr::Time) -> Result<Self::InputInfo> {
+        let menu_button = self.buttons.read(xr_shell, "menu_button", &self.menu_button, xr::Path::NULL, time)?;
+        Ok(SimpleInput {
+            lh: self.locate_hand(xr_shell, &self.hands.lh, space, time)?,
+            rh: self.locate_hand(xr_shell, &self.hands.rh, space, time)?,
+            menu_button,
+        })
+    }
+
+    fn apply(&self, xr_shell: &XrShell, output: &Self::OutputInfo) -> Result<()> {
+        self.hands.apply_haptics(xr_shell, output)
+    }
+}
+
+/// Everything one Touch controller reports
+#[derive(Debug, Clone, Copy)]
+pub struct TouchHand {
+    /// Resolved according to [PoseStates::policy]
+    pub grip: Pose,
+    pub grip_tracking: TrackingFlags,
+    /// Resolved according to [PoseStates::policy]
+    pub aim: Pose,
+    pub aim_tracking: TrackingFlags,
+    pub grip_velocity: Velocity,
+    pub aim_velocity: Velocity,
+
+    /// Shaped by [AnalogConfig::trigger]
+    pub trigger: f32,
+    /// The raw trigger through [AnalogConfig::trigger_button]
+    pub trigger_button: ButtonState,
+    pub trigger_touch: ButtonState,
+    /// Shaped by [AnalogConfig::squeeze]
+    pub squeeze: f32,
+    /// The raw squeeze through [AnalogConfig::squeeze_button]
+    pub squeeze_button: ButtonState,
+
+    /// Shaped by [AnalogConfig::thumbstick]: +X right and +Y forward
+    pub thumbstick: [f32; 2],
+    pub thumbstick_click: ButtonState,
+    pub thumbstick_touch: ButtonState,
+    pub thumbrest_touch: ButtonState,
+
+    /// A on the right controller, X on the left
+    pub primary_button: ButtonState,
+    pub primary_button_touch: ButtonState,
+    /// B on the right controller, Y on the left
+    pub secondary_button: ButtonState,
+    pub secondary_button_touch: ButtonState,
+}
+
+pub struct TouchInput {
+    pub lh: Option<TouchHand>,
+    pub rh: Option<TouchHand>,
+    /// The menu button on the left controller. The right one is reserved for the system.
+    pub menu_button: ButtonState,
+}
+
+/// Oculus/Meta Touch controllers, as on the Quest 1-3
+///
+/// "/interaction_profiles/oculus/touch_controller"
 /// https://community.khronos.org/t/quest-3-controllers-with-steamvr/111048
 /// https://en.wikipedia.org/wiki/Oculus_Touch
-struct OculusTouchControllers();
+pub struct OculusTouchControllers {
+    hands: HandActions,
+
+    trigger: xr::Action<f32>,
+    trigger_touch: xr::Action<bool>,
+    squeeze: xr::Action<f32>,
+
+    thumbstick: xr::Action<xr::Vector2f>,
+    thumbstick_click: xr::Action<bool>,
+    thumbstick_touch: xr::Action<bool>,
+    thumbrest_touch: xr::Action<bool>,
+
+    primary_button: xr::Action<bool>,
+    primary_button_touch: xr::Action<bool>,
+    secondary_button: xr::Action<bool>,
+    secondary_button_touch: xr::Action<bool>,
+
+    menu_button: xr::Action<bool>,
+
+    /// Applied to the analog actions in [Controls::locate]
+    pub analog: AnalogConfig,
+    pub buttons: ButtonStates,
+    pub poses: PoseStates,
+}
+impl OculusTouchControllers {
+    pub const INTERACTION_PROFILE: &'static str = "/interaction_profiles/oculus/touch_controller";
+
+    pub fn new(xr_shell: &XrShell, action_set_name: &'static str, localized_name: &'static str) -> Result<Self> {
+        let hands = HandActions::new(xr_shell, action_set_name, localized_name)?;
+        let both_hands = hands.both_hands();
+        let action_set = &hands.action_set;
+
+        let trigger = action_set.create_action::<f32>("trigger", "Trigger", &both_hands)?;
+        let trigger_touch = action_set.create_action::<bool>("trigger_touch", "Trigger Touch", &both_hands)?;
+        let squeeze = action_set.create_action::<f32>("squeeze", "Squeeze", &both_hands)?;
+
+        let thumbstick = action_set.create_action::<xr::Vector2f>("thumbstick", "Thumbstick", &both_hands)?;
+        let thumbstick_click =
+            action_set.create_action::<bool>("thumbstick_click", "Thumbstick Click", &both_hands)?;
+        let thumbstick_touch =
+            action_set.create_action::<bool>("thumbstick_touch", "Thumbstick Touch", &both_hands)?;
+        let thumbrest_touch =
+            action_set.create_action::<bool>("thumbrest_touch", "Thumbrest Touch", &both_hands)?;
+
+        let primary_button =
+            action_set.create_action::<bool>("primary_button", "A/X Button", &both_hands)?;
+        let primary_button_touch =
+            action_set.create_action::<bool>("primary_button_touch", "A/X Button Touch", &both_hands)?;
+        let secondary_button =
+            action_set.create_action::<bool>("secondary_button", "B/Y Button", &both_hands)?;
+        let secondary_button_touch =
+            action_set.create_action::<bool>("secondary_button_touch", "B/Y Button Touch", &both_hands)?;
+
+        let menu_button = action_set.create_action::<bool>("menu_button", "Menu Button", &[])?;
+
+        Ok(Self {
+            hands,
+
+            trigger,
+            trigger_touch,
+            squeeze,
+
+            thumbstick,
+            thumbstick_click,
+            thumbstick_touch,
+            thumbrest_touch,
+
+            primary_button,
+            primary_button_touch,
+            secondary_button,
+            secondary_button_touch,
+
+            menu_button,
+
+            analog: AnalogConfig::default(),
+            buttons: ButtonStates::default(),
+            poses: PoseStates::default(),
+        })
+    }
+
+    fn locate_hand(
+        &self,
+        xr_shell: &XrShell,
+        hand: &HandSpaces,
+        space: &xr::Space,
+        time: xr::Time,
+    ) -> Result<Option<TouchHand>> {
+        let Some(located) = self.hands.locate_hand(xr_shell, &self.poses, hand, space, time)? else {
+            return Ok(None);
+        };
+        let session = &xr_shell.xr_session;
+        let trigger = self.trigger.state(session, hand.subpath)?;
+        let squeeze = self.squeeze.state(session, hand.subpath)?;
+        let thumbstick = self.thumbstick.state(session, hand.subpath)?.current_state;
+        let button = |name, action| self.buttons.read(xr_shell, name, action, hand.subpath, time);
+        let analog_button = |name, state, threshold| self.buttons.update_analog(name, hand.subpath, state, threshold, time);
+
+        Ok(Some(TouchHand {
+            grip: located.grip,
+            grip_tracking: located.grip_tracking,
+            aim: located.aim,
+            aim_tracking: located.aim_tracking,
+            grip_velocity: located.grip_velocity,
+            aim_velocity: located.aim_velocity,
+
+            trigger: self.analog.trigger.apply(trigger.current_state),
+            trigger_button: analog_button("trigger", &trigger, &self.analog.trigger_button),
+            trigger_touch: button("trigger_touch", &self.trigger_touch)?,
+            squeeze: self.analog.squeeze.apply(squeeze.current_state),
+            squeeze_button: analog_button("squeeze", &squeeze, &self.analog.squeeze_button),
+
+            thumbstick: self.analog.thumbstick.apply([thumbstick.x, thumbstick.y]),
+            thumbstick_click: button("thumbstick_click", &self.thumbstick_click)?,
+            thumbstick_touch: button("thumbstick_touch", &self.thumbstick_touch)?,
+            thumbrest_touch: button("thumbrest_touch", &self.thumbrest_touch)?,
+
+            primary_button: button("primary_button", &self.primary_button)?,
+            primary_button_touch: button("primary_button_touch", &self.primary_button_touch)?,
+            secondary_button: button("secondary_button", &self.secondary_button)?,
+            secondary_button_touch: button("secondary_button_touch", &self.secondary_button_touch)?,
+        }))
+    }
+}
+impl Controls for OculusTouchControllers {
+    type InputInfo = TouchInput;
+
+    type OutputInfo = HapticOutput;
+
+    fn action_set(&self) -> &xr::ActionSet {
+        &self.hands.action_set
+    }
+
+    fn suggested_bindings(&self, xr_instance: &xr::Instance) -> Result<Vec<(
+        &str,
+        Vec<xr::Binding<'_>>
+    )>> {
+        let path = |path: &str| xr_instance.string_to_path(path);
+        let mut bindings = vec![];
+        for hand in ["left", "right"] {
+            // The face buttons are X/Y on the left controller and A/B on the right
+            let (primary, secondary) = if hand == "left" { ("x", "y") } else { ("a", "b") };
+            bindings.extend(self.hands.bindings(xr_instance, hand)?);
+            bindings.extend([
+                xr::Binding::new(&self.trigger, path(&format!("/user/hand/{hand}/input/trigger/value"))?),
+                xr::Binding::new(&self.trigger_touch, path(&format!("/user/hand/{hand}/input/trigger/touch"))?),
+                xr::Binding::new(&self.squeeze, path(&format!("/user/hand/{hand}/input/squeeze/value"))?),
+
+                xr::Binding::new(&self.thumbstick, path(&format!("/user/hand/{hand}/input/thumbstick"))?),
+                xr::Binding::new(&self.thumbstick_click, path(&format!("/user/hand/{hand}/input/thumbstick/click"))?),
+                xr::Binding::new(&self.thumbstick_touch, path(&format!("/user/hand/{hand}/input/thumbstick/touch"))?),
+                xr::Binding::new(&self.thumbrest_touch, path(&format!("/user/hand/{hand}/input/thumbrest/touch"))?),
+
+                xr::Binding::new(&self.primary_button, path(&format!("/user/hand/{hand}/input/{primary}/click"))?),
+                xr::Binding::new(&self.primary_button_touch, path(&format!("/user/hand/{hand}/input/{primary}/touch"))?),
+                xr::Binding::new(&self.secondary_button, path(&format!("/user/hand/{hand}/input/{secondary}/click"))?),
+                xr::Binding::new(&self.secondary_button_touch, path(&format!("/user/hand/{hand}/input/{secondary}/touch"))?),
+            ]);
+        }
+        bindings.push(xr::Binding::new(&self.menu_button, path("/user/hand/left/input/menu/click")?));
+
+        Ok(vec![(Self::INTERACTION_PROFILE, bindings)])
+    }
+
+    fn locate(&self, xr_shell: &XrShell, space: &xr::Space, time: xr::Time) -> Result<Self::InputInfo> {
+        let menu_button = self.buttons.read(xr_shell, "menu_button", &self.menu_button, xr::Path::NULL, time)?;
+        Ok(TouchInput {
+            lh: self.locate_hand(xr_shell, &self.hands.lh, space, time)?,
+            rh: self.locate_hand(xr_shell, &self.hands.rh, space, time)?,
+            menu_button,
+        })
+    }
+
+    fn apply(&self, xr_shell: &XrShell, output: &Self::OutputInfo) -> Result<()> {
+        self.hands.apply_haptics(xr_shell, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Rotation3};
+
+    use super::*;
+
+    const SUBPATH: xr::Path = xr::Path::NULL;
+
+    fn time(secs: f64) -> xr::Time {
+        xr::Time::from_nanos((secs * 1e9) as i64)
+    }
+
+    /// What a sync reports for an active button which is `held`, and last changed at `last_change`
+    fn state(held: bool, changed: bool, last_change: f64) -> xr::ActionState<bool> {
+        xr::ActionState {
+            current_state: held,
+            changed_since_last_sync: changed,
+            last_change_time: time(last_change),
+            is_active: true,
+        }
+    }
+
+    #[test]
+    fn press_and_release_are_edges() {
+        let buttons = ButtonStates::default();
+        let down = buttons.update("a", SUBPATH, &state(true, true, 1.0), time(1.01));
+        assert!(down.held && down.pressed && !down.released);
+
+        let still_down = buttons.update("a", SUBPATH, &state(true, false, 1.0), time(1.02));
+        assert!(still_down.held && !still_down.pressed);
+        assert!((still_down.held_secs - 0.02).abs() < 1e-4);
+
+        let up = buttons.update("a", SUBPATH, &state(false, true, 1.03), time(1.04));
+        assert!(!up.held && !up.pressed && up.released);
+        assert_eq!(up.held_secs, 0.0);
+    }
+
+    #[test]
+    fn inactive_reads_as_released() {
+        let buttons = ButtonStates::default();
+        let inactive = xr::ActionState { is_active: false, ..state(true, true, 1.0) };
+        assert_eq!(buttons.update("a", SUBPATH, &inactive, time(1.0)), ButtonState::default());
+    }
 
+    #[test]
+    fn long_press_fires_once_per_press() {
+        let buttons = ButtonStates::default();
+        let long = buttons.timing.long_press_secs as f64;
+        buttons.update("a", SUBPATH, &state(true, true, 1.0), time(1.0));
+        assert!(!buttons.update("a", SUBPATH, &state(true, false, 1.0), time(1.0 + long - 0.01)).long_press);
+        assert!(buttons.update("a", SUBPATH, &state(true, false, 1.0), time(1.0 + long + 0.01)).long_press);
+        assert!(!buttons.update("a", SUBPATH, &state(true, false, 1.0), time(1.0 + long + 0.02)).long_press);
+
+        // A new press can fire again
+        buttons.update("a", SUBPATH, &state(false, true, 2.0), time(2.0));
+        buttons.update("a", SUBPATH, &state(true, true, 3.0), time(3.0));
+        assert!(buttons.update("a", SUBPATH, &state(true, false, 3.0), time(3.0 + long + 0.01)).long_press);
+    }
+
+    #[test]
+    fn double_click_measured_between_presses() {
+        let buttons = ButtonStates::default();
+        let window = buttons.timing.double_click_secs as f64;
+        assert!(!buttons.update("a", SUBPATH, &state(true, true, 1.0), time(1.0)).double_click);
+        buttons.update("a", SUBPATH, &state(false, true, 1.05), time(1.05));
+        // The second press is inside the window even though it's synced well after it
+        let second = buttons.update("a", SUBPATH, &state(true, true, 1.0 + window - 0.01), time(1.0 + window + 0.1));
+        assert!(second.pressed && second.double_click);
+
+        // The press after a double click starts a new pair
+        buttons.update("a", SUBPATH, &state(false, true, 1.0 + window), time(1.0 + window));
+        let third = buttons.update("a", SUBPATH, &state(true, true, 1.0 + window + 0.05), time(1.0 + window + 0.05));
+        assert!(third.pressed && !third.double_click);
+    }
+
+    #[test]
+    fn slow_second_press_is_not_a_double_click() {
+        let buttons = ButtonStates::default();
+        let window = buttons.timing.double_click_secs as f64;
+        buttons.update("a", SUBPATH, &state(true, true, 1.0), time(1.0));
+        buttons.update("a", SUBPATH, &state(false, true, 1.05), time(1.05));
+        assert!(!buttons.update("a", SUBPATH, &state(true, true, 1.0 + window + 0.01), time(1.0 + window + 0.01)).double_click);
+    }
+
+    #[test]
+    fn buttons_are_tracked_separately() {
+        let buttons = ButtonStates::default();
+        buttons.update("a", SUBPATH, &state(true, true, 1.0), time(1.0));
+        buttons.update("a", SUBPATH, &state(false, true, 1.05), time(1.05));
+        assert!(!buttons.update("b", SUBPATH, &state(true, true, 1.1), time(1.1)).double_click);
+    }
+
+    /// What a sync reports for an active analog action at `value`, which last changed at `last_change`
+    fn analog(value: f32, last_change: f64) -> xr::ActionState<f32> {
+        xr::ActionState {
+            current_state: value,
+            changed_since_last_sync: true,
+            last_change_time: time(last_change),
+            is_active: true,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < EPSILON, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn curves_keep_the_ends() {
+        for curve in [ResponseCurve::Linear, ResponseCurve::Power(2.0), ResponseCurve::Power(0.5)] {
+            assert_close(curve.apply(0.0), 0.0);
+            assert_close(curve.apply(1.0), 1.0);
+        }
+        assert_close(ResponseCurve::Linear.apply(0.3), 0.3);
+        assert_close(ResponseCurve::Power(2.0).apply(0.5), 0.25);
+    }
+
+    #[test]
+    fn axis_dead_zone_edge() {
+        let axis = AxisConfig::default();
+        assert_eq!(axis.apply(0.0), 0.0);
+        assert_eq!(axis.apply(axis.dead_zone), 0.0);
+        let just_above = axis.apply(axis.dead_zone + 0.01);
+        assert!(just_above > 0.0 && just_above < 0.05);
+    }
+
+    #[test]
+    fn axis_saturates() {
+        let axis = AxisConfig::default();
+        assert_close(axis.apply(axis.saturation), 1.0);
+        assert_close(axis.apply(1.0), 1.0);
+        assert_close(axis.apply(1.5), 1.0);
+        assert_eq!(axis.apply(-0.5), 0.0);
+    }
+
+    #[test]
+    fn axis_curve_applies_after_the_dead_zone() {
+        let axis = AxisConfig { dead_zone: 0.1, saturation: 0.9, curve: ResponseCurve::Power(2.0) };
+        assert_close(axis.apply(0.5), 0.25);
+    }
+
+    #[test]
+    fn stick_dead_zone_is_radial() {
+        let stick = StickConfig::default();
+        assert_eq!(stick.apply([0.1, 0.1]), [0.0, 0.0]);
+        // Either axis alone is inside the dead zone, but together they're out of it
+        let [x, y] = stick.apply([0.12, 0.12]);
+        assert!(x > 0.0 && y > 0.0);
+        // A small movement off-axis is kept rather than snapped to the axis
+        let [x, y] = stick.apply([0.5, 0.05]);
+        assert!(x > 0.0 && y > 0.0);
+        assert_close(y / x, 0.1);
+    }
+
+    #[test]
+    fn stick_keeps_direction() {
+        let stick = StickConfig { curve: ResponseCurve::Power(2.0), ..Default::default() };
+        for raw in [[0.3, -0.4], [-0.3, 0.4], [-0.3, -0.4], [-0.6, 0.0]] {
+            let [x, y] = stick.apply(raw);
+            assert!(x.signum() == raw[0].signum() || raw[0] == 0.0, "{raw:?} -> {x}");
+            assert!(y.signum() == raw[1].signum() || raw[1] == 0.0, "{raw:?} -> {y}");
+            assert_close(x * raw[1], y * raw[0]);
+        }
+    }
+
+    #[test]
+    fn stick_saturates() {
+        let stick = StickConfig::default();
+        let [x, y] = stick.apply([-0.8, 0.6]);
+        assert_close(x.hypot(y), 1.0);
+        assert_close(x, -0.8);
+        let [x, y] = stick.apply([0.0, stick.saturation]);
+        assert_close(x, 0.0);
+        assert_close(y, 1.0);
+    }
+
+    #[test]
+    fn threshold_has_hysteresis() {
+        let threshold = Threshold::default();
+        let between = (threshold.press + threshold.release) / 2.0;
+        assert!(!threshold.apply(between, false));
+        assert!(threshold.apply(between, true));
+        assert!(threshold.apply(threshold.press, false));
+        assert!(!threshold.apply(threshold.release - 0.01, true));
+    }
+
+    #[test]
+    fn analog_button_presses_at_threshold() {
+        let buttons = ButtonStates::default();
+        let threshold = Threshold::default();
+        let half = buttons.update_analog("squeeze", SUBPATH, &analog(0.5, 1.0), &threshold, time(1.0));
+        assert!(!half.held && !half.pressed);
+
+        let down = buttons.update_analog("squeeze", SUBPATH, &analog(0.8, 1.1), &threshold, time(1.1));
+        assert!(down.held && down.pressed);
+        // Still held between the two thresholds, and timed from the press
+        let wobble = buttons.update_analog("squeeze", SUBPATH, &analog(0.7, 1.2), &threshold, time(1.3));
+        assert!(wobble.held && !wobble.pressed);
+        assert_close(wobble.held_secs, 0.2);
+
+        let up = buttons.update_analog("squeeze", SUBPATH, &analog(0.6, 1.4), &threshold, time(1.4));
+        assert!(!up.held && up.released);
+    }
+
+    #[test]
+    fn inactive_analog_button_is_released() {
+        let buttons = ButtonStates::default();
+        let threshold = Threshold::default();
+        buttons.update_analog("squeeze", SUBPATH, &analog(1.0, 1.0), &threshold, time(1.0));
+        let inactive = xr::ActionState { is_active: false, ..analog(1.0, 1.0) };
+        let up = buttons.update_analog("squeeze", SUBPATH, &inactive, &threshold, time(1.1));
+        assert!(!up.held && up.released);
+    }
+
+    const EPSILON: f32 = 1e-4;
+
+    /// At `x` along +X, turned `yaw` radians around +Y
+    fn pose(x: f32, yaw: f32) -> Pose {
+        Pose {
+            position: Vec3([x, 0.0, 0.0]),
+            orientation: cgmath::Quaternion::from_angle_y(cgmath::Rad(yaw)).into(),
+        }
+    }
+
+    fn location(flags: xr::SpaceLocationFlags, pose: Pose) -> xr::SpaceLocation {
+        xr::SpaceLocation {
+            location_flags: flags,
+            pose: xr::Posef {
+                orientation: pose.orientation.into(),
+                position: pose.position.into(),
+            },
+        }
+    }
+
+    fn valid(pose: Pose) -> xr::SpaceLocation {
+        location(xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID, pose)
+    }
+
+    fn velocity(linear: Option<[f32; 3]>) -> xr::SpaceVelocity {
+        let zero = xr::Vector3f { x: 0.0, y: 0.0, z: 0.0 };
+        xr::SpaceVelocity {
+            velocity_flags: if linear.is_some() {
+                xr::SpaceVelocityFlags::LINEAR_VALID
+            } else {
+                xr::SpaceVelocityFlags::EMPTY
+            },
+            linear_velocity: linear.map_or(zero, |v| Vec3(v).into()),
+            angular_velocity: zero,
+        }
+    }
+
+    fn assert_position(pose: Pose, x: f32) {
+        let position: cgmath::Vector3<f32> = pose.position.into();
+        assert!((position - cgmath::Vector3::new(x, 0.0, 0.0)).magnitude() < EPSILON, "{pose:?} isn't at x = {x}");
+    }
+
+    fn assert_yaw(pose: Pose, yaw: f32) {
+        let orientation: cgmath::Quaternion<f32> = pose.orientation.into();
+        let expected = cgmath::Quaternion::from_angle_y(cgmath::Rad(yaw));
+        // q and -q are the same rotation
+        assert!(orientation.dot(expected).abs() > 1.0 - EPSILON, "{pose:?} isn't turned {yaw}");
+    }
+
+    fn poses(policy: InvalidPosePolicy) -> PoseStates {
+        PoseStates {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    const INVALID: xr::SpaceLocationFlags = xr::SpaceLocationFlags::EMPTY;
+
+    #[test]
+    fn valid_pose_is_reported_under_every_policy() {
+        for policy in [
+            InvalidPosePolicy::Hide,
+            InvalidPosePolicy::HoldLast,
+            InvalidPosePolicy::Extrapolate { max_secs: 1.0 },
+        ] {
+            let resolved = poses(policy).resolve("grip", SUBPATH, &valid(pose(1.0, 0.5)), &velocity(None), time(1.0));
+            assert_position(resolved.unwrap(), 1.0);
+            assert_yaw(resolved.unwrap(), 0.5);
+        }
+    }
+
+    #[test]
+    fn hide_drops_invalid_pose() {
+        let poses = poses(InvalidPosePolicy::Hide);
+        poses.resolve("grip", SUBPATH, &valid(pose(1.0, 0.0)), &velocity(None), time(1.0));
+        assert!(poses.resolve("grip", SUBPATH, &location(INVALID, pose(0.0, 0.0)), &velocity(None), time(1.1)).is_none());
+    }
+
+    #[test]
+    fn nothing_to_hold_before_first_valid_pose() {
+        let poses = poses(InvalidPosePolicy::HoldLast);
+        assert!(poses.resolve("grip", SUBPATH, &location(INVALID, pose(0.0, 0.0)), &velocity(None), time(1.0)).is_none());
+    }
+
+    #[test]
+    fn hold_last_replaces_only_the_invalid_half() {
+        let poses = poses(InvalidPosePolicy::HoldLast);
+        poses.resolve("grip", SUBPATH, &valid(pose(1.0, 0.0)), &velocity(None), time(1.0));
+
+        let held = poses.resolve("grip", SUBPATH, &location(INVALID, pose(5.0, 0.7)), &velocity(None), time(2.0));
+        assert_position(held.unwrap(), 1.0);
+        assert_yaw(held.unwrap(), 0.0);
+
+        // Lost position but still has orientation, like a 3DoF fallback
+        let orientation_only = location(xr::SpaceLocationFlags::ORIENTATION_VALID, pose(5.0, 0.7));
+        let held = poses.resolve("grip", SUBPATH, &orientation_only, &velocity(None), time(2.0));
+        assert_position(held.unwrap(), 1.0);
+        assert_yaw(held.unwrap(), 0.7);
+    }
+
+    #[test]
+    fn extrapolate_uses_reported_velocity_until_max_secs() {
+        let poses = poses(InvalidPosePolicy::Extrapolate { max_secs: 1.0 });
+        poses.resolve("grip", SUBPATH, &valid(pose(0.0, 0.0)), &velocity(Some([2.0, 0.0, 0.0])), time(1.0));
+
+        let carried = poses.resolve("grip", SUBPATH, &location(INVALID, pose(0.0, 0.0)), &velocity(None), time(1.5));
+        assert_position(carried.unwrap(), 1.0);
+        assert!(poses.resolve("grip", SUBPATH, &location(INVALID, pose(0.0, 0.0)), &velocity(None), time(2.1)).is_none());
+    }
+
+    #[test]
+    fn extrapolate_estimates_velocity_the_runtime_doesnt_report() {
+        let poses = poses(InvalidPosePolicy::Extrapolate { max_secs: 1.0 });
+        poses.resolve("grip", SUBPATH, &valid(pose(0.0, 0.0)), &velocity(None), time(1.0));
+        poses.resolve("grip", SUBPATH, &valid(pose(1.0, 0.0)), &velocity(None), time(1.5));
+
+        let carried = poses.resolve("grip", SUBPATH, &location(INVALID, pose(0.0, 0.0)), &velocity(None), time(2.0));
+        assert_position(carried.unwrap(), 2.0);
+    }
+
+    #[test]
+    fn forget_drops_the_held_pose() {
+        let poses = poses(InvalidPosePolicy::HoldLast);
+        poses.resolve("grip", SUBPATH, &valid(pose(1.0, 0.0)), &velocity(None), time(1.0));
+        poses.forget(SUBPATH);
+        assert!(poses.resolve("grip", SUBPATH, &location(INVALID, pose(0.0, 0.0)), &velocity(None), time(1.1)).is_none());
+    }
+
+    #[test]
+    fn good_pose_extrapolates_position_and_rotation() {
+        let good = GoodPose {
+            pose: pose(1.0, 0.0),
+            time: time(0.0),
+            linear_velocity: cgmath::Vector3::new(2.0, 0.0, 0.0),
+            angular_velocity: cgmath::Vector3::new(0.0, std::f32::consts::PI, 0.0),
+        };
+        let extrapolated = good.extrapolate(0.5);
+        assert_position(extrapolated, 2.0);
+        assert_yaw(extrapolated, std::f32::consts::FRAC_PI_2);
+
+        assert_yaw(good.extrapolate(0.0), 0.0);
+    }
+
+    #[test]
+    fn velocities_are_estimated_between_poses() {
+        let (linear, angular) = estimate_velocities(pose(0.0, 0.0), pose(1.0, std::f32::consts::FRAC_PI_2), 0.5);
+        assert!((linear - cgmath::Vector3::new(2.0, 0.0, 0.0)).magnitude() < EPSILON);
+        assert!((angular - cgmath::Vector3::new(0.0, std::f32::consts::PI, 0.0)).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn velocity_estimate_takes_the_short_way_round() {
+        // -q is the same orientation as q, so there's no rotation
+        let flipped = Pose {
+            orientation: (-cgmath::Quaternion::from_angle_y(cgmath::Rad(0.0f32))).into(),
+            ..pose(0.0, 0.0)
+        };
+        let (_, angular) = estimate_velocities(pose(0.0, 0.0), flipped, 0.5);
+        assert!(angular.magnitude() < EPSILON);
+
+        // Three quarters of a turn one way is a quarter turn the other
+        let (_, angular) = estimate_velocities(pose(0.0, 0.0), pose(0.0, 1.5 * std::f32::consts::PI), 1.0);
+        assert!((angular - cgmath::Vector3::new(0.0, -std::f32::consts::FRAC_PI_2, 0.0)).magnitude() < EPSILON);
+    }
+}
@@ -0,0 +1,468 @@
+//! Layout and point-and-click interaction for a gallery of image panels.
+//!
+//! The gallery shows one page of images at a time, each on a [Panel] sized to the image's aspect ratio.
+//! With either hand's pointer:
+//! - click on the right half of a panel for the next page, or the left half for the previous one,
+//! - hold click on a panel to grab it, and move the hand to move it,
+//! - grab a panel with both hands and move them apart or together to scale it.
+//!
+//! This module has no GPU state; the game loads the images for [Gallery::page_images] and draws the [Gallery::panels].
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use cgmath::{InnerSpace, Vector3, Vector4};
+
+use crate::math::Mat4;
+
+/// File extensions [list_images] picks up, compared case-insensitively
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "ktx2"];
+
+/// Every image in `dir` (not recursive), sorted by file name
+pub fn list_images(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Listing {}", dir.display()))? {
+        let path = entry?.path();
+        let is_image = path.extension().is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        });
+        if is_image && path.is_file() {
+            images.push(path);
+        }
+    }
+    images.sort();
+    Ok(images)
+}
+
+/// A flat rectangle facing +Z in its own space, centered on its origin
+#[derive(Debug, Clone, Copy)]
+pub struct Panel {
+    /// Rigid transform, without the panel's size
+    pub world_from_panel: Mat4,
+    /// Meters
+    pub height: f32,
+    /// Width / height, from the image shown on it
+    pub aspect_ratio: f32,
+}
+impl Panel {
+    pub const MIN_HEIGHT: f32 = 0.1;
+    pub const MAX_HEIGHT: f32 = 5.0;
+
+    pub fn width(&self) -> f32 {
+        self.height * self.aspect_ratio
+    }
+
+    /// Where the pointer ray hits the panel, if it does
+    pub fn hit(&self, world_from_pointer: Mat4) -> Option<PanelHit> {
+        let panel_from_pointer = self.world_from_panel.inverse()?.as_cg() * world_from_pointer.as_cg();
+        // The pointer looks down its -Z
+        let origin = (panel_from_pointer * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate();
+        let direction = (panel_from_pointer * Vector4::new(0.0, 0.0, -1.0, 0.0)).truncate();
+        if direction.z.abs() < f32::EPSILON {
+            return None;
+        }
+        let distance = -origin.z / direction.z;
+        if distance < 0.0 {
+            return None;
+        }
+        let hit = origin + direction * distance;
+        let u = hit.x / self.width() + 0.5;
+        let v = hit.y / self.height + 0.5;
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some(PanelHit { distance, u, v })
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PanelHit {
+    /// Along the pointer ray, in meters
+    pub distance: f32,
+    /// 0 at the left edge, 1 at the right
+    pub u: f32,
+    /// 0 at the bottom edge, 1 at the top
+    pub v: f32,
+}
+
+/// One hand's pointer this frame
+#[derive(Debug, Clone, Copy)]
+pub struct HandPointer {
+    pub world_from_pointer: Mat4,
+    pub click: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HandState {
+    Idle,
+    /// Click went down over a panel, and hasn't been released
+    Pressed {
+        panel: usize,
+        pointer_from_panel: Mat4,
+        pressed_at_secs: f64,
+        pressed_position: Vector3<f32>,
+        hit: PanelHit,
+        /// Once a press has moved or been held it's a grab, and won't page on release
+        grabbing: bool,
+    },
+    /// Click went down over nothing, ignored until released
+    Missed,
+}
+
+/// Scaling with both hands on one panel
+#[derive(Debug, Clone, Copy)]
+struct TwoHandScale {
+    panel: usize,
+    initial_hand_distance: f32,
+    initial_height: f32,
+}
+
+pub struct Gallery {
+    images: Vec<PathBuf>,
+    page: usize,
+    pub panels: Vec<Panel>,
+    hands: [HandState; 2],
+    scale: Option<TwoHandScale>,
+}
+
+impl Gallery {
+    pub const PANELS_PER_PAGE: usize = 3;
+    /// Height of newly laid out panels, in meters
+    pub const DEFAULT_PANEL_HEIGHT: f32 = 0.6;
+    /// Panels are laid out on an arc around the origin, at roughly eye height
+    pub const LAYOUT_RADIUS: f32 = 1.5;
+    pub const LAYOUT_HEIGHT: f32 = 1.4;
+
+    /// Presses shorter than this which don't move further than [Gallery::CLICK_MAX_MOVE] are clicks
+    pub const CLICK_MAX_SECS: f64 = 0.35;
+    pub const CLICK_MAX_MOVE: f32 = 0.02;
+
+    /// With no images, there's still a single panel for a placeholder
+    pub fn new(images: Vec<PathBuf>) -> Self {
+        let panel_count = images.len().clamp(1, Self::PANELS_PER_PAGE);
+        let mut gallery = Self {
+            images,
+            page: 0,
+            panels: vec![],
+            hands: [HandState::Idle; 2],
+            scale: None,
+        };
+        gallery.panels = (0..panel_count).map(|i| gallery.default_panel(i)).collect();
+        gallery
+    }
+
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let images = list_images(dir)?;
+        log::info!("Found {} images in {}", images.len(), dir.display());
+        Ok(Self::new(images))
+    }
+
+    fn default_panel(&self, slot: usize) -> Panel {
+        // Spread the panels evenly either side of -Z, each turned to face the origin
+        let spacing = (Self::DEFAULT_PANEL_HEIGHT * 1.6) / Self::LAYOUT_RADIUS;
+        let panel_count = self.images.len().clamp(1, Self::PANELS_PER_PAGE);
+        let yaw = (panel_count as f32 - 1.0) * 0.5 * spacing - slot as f32 * spacing;
+        Panel {
+            world_from_panel: Mat4::from_rotation_y(yaw)
+                * Mat4::from_translation([0.0, Self::LAYOUT_HEIGHT, -Self::LAYOUT_RADIUS]),
+            height: Self::DEFAULT_PANEL_HEIGHT,
+            aspect_ratio: 1.0,
+        }
+    }
+
+    /// Put every panel back where it started, at the default size
+    pub fn reset_layout(&mut self) {
+        for slot in 0..self.panels.len() {
+            let aspect_ratio = self.panels[slot].aspect_ratio;
+            self.panels[slot] = Panel {
+                aspect_ratio,
+                ..self.default_panel(slot)
+            };
+        }
+        self.hands = [HandState::Idle; 2];
+        self.scale = None;
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.images.len().div_ceil(Self::PANELS_PER_PAGE).max(1)
+    }
+
+    /// The image for each panel on the current page, or `None` for panels past the last image
+    pub fn page_images(&self) -> Vec<Option<&Path>> {
+        (0..self.panels.len())
+            .map(|slot| {
+                self.images
+                    .get(self.page * Self::PANELS_PER_PAGE + slot)
+                    .map(PathBuf::as_path)
+            })
+            .collect()
+    }
+
+    pub fn next_page(&mut self) {
+        self.page = (self.page + 1) % self.page_count();
+    }
+
+    pub fn previous_page(&mut self) {
+        self.page = (self.page + self.page_count() - 1) % self.page_count();
+    }
+
+    /// The nearest panel the pointer ray hits
+    pub fn pointer_hit(&self, world_from_pointer: Mat4) -> Option<(usize, PanelHit)> {
+        self.panels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, panel)| panel.hit(world_from_pointer).map(|hit| (i, hit)))
+            .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Advance the interaction with the left and right hands' pointers, `None` while untracked.
+    /// Returns whether each hand's click went down on a panel, e.g. for haptic feedback.
+    pub fn update(&mut self, hands: [Option<HandPointer>; 2], time_secs: f64) -> [bool; 2] {
+        let mut landed = [false; 2];
+        for (hand, pointer) in hands.iter().enumerate() {
+            landed[hand] = self.update_hand(hand, *pointer, time_secs);
+        }
+        self.update_scale(&hands);
+        landed
+    }
+
+    /// Whether the click went down on a panel
+    fn update_hand(&mut self, hand: usize, pointer: Option<HandPointer>, time_secs: f64) -> bool {
+        // Losing tracking lets go without clicking
+        let Some(pointer) = pointer else {
+            self.hands[hand] = HandState::Idle;
+            return false;
+        };
+        let was_idle = matches!(self.hands[hand], HandState::Idle);
+        let position = position_of(pointer.world_from_pointer);
+
+        self.hands[hand] = match (self.hands[hand], pointer.click) {
+            (HandState::Idle, true) => match self.pointer_hit(pointer.world_from_pointer) {
+                Some((panel, hit)) => HandState::Pressed {
+                    panel,
+                    pointer_from_panel: pointer.world_from_pointer.inverse().unwrap_or(Mat4::identity())
+                        * self.panels[panel].world_from_panel,
+                    pressed_at_secs: time_secs,
+                    pressed_position: position,
+                    hit,
+                    grabbing: false,
+                },
+                None => HandState::Missed,
+            },
+            (HandState::Idle, false) => HandState::Idle,
+            (HandState::Missed, true) => HandState::Missed,
+            (HandState::Missed, false) => HandState::Idle,
+            (
+                HandState::Pressed {
+                    panel,
+                    pointer_from_panel,
+                    pressed_at_secs,
+                    pressed_position,
+                    hit,
+                    grabbing,
+                },
+                true,
+            ) => {
+                let grabbing = grabbing
+                    || time_secs - pressed_at_secs > Self::CLICK_MAX_SECS
+                    || (position - pressed_position).magnitude() > Self::CLICK_MAX_MOVE;
+                // While both hands hold this panel to scale it, only the left hand moves it,
+                // and the right hand follows so it can carry on alone if the left lets go
+                let moved_by_other_hand = hand == 1 && self.scale.is_some_and(|s| s.panel == panel);
+                let pointer_from_panel = if moved_by_other_hand {
+                    pointer.world_from_pointer.inverse().unwrap_or(Mat4::identity())
+                        * self.panels[panel].world_from_panel
+                } else {
+                    if grabbing {
+                        self.panels[panel].world_from_panel = pointer.world_from_pointer * pointer_from_panel;
+                    }
+                    pointer_from_panel
+                };
+                HandState::Pressed {
+                    panel,
+                    pointer_from_panel,
+                    pressed_at_secs,
+                    pressed_position,
+                    hit,
+                    grabbing,
+                }
+            }
+            (HandState::Pressed { hit, grabbing, .. }, false) => {
+                if !grabbing {
+                    if hit.u >= 0.5 {
+                        self.next_page();
+                    } else {
+                        self.previous_page();
+                    }
+                }
+                HandState::Idle
+            }
+        };
+        was_idle && matches!(self.hands[hand], HandState::Pressed { .. })
+    }
+
+    fn update_scale(&mut self, hands: &[Option<HandPointer>; 2]) {
+        let both_on = match (self.hands[0], self.hands[1]) {
+            (HandState::Pressed { panel: a, .. }, HandState::Pressed { panel: b, .. }) if a == b => Some(a),
+            _ => None,
+        };
+        let (Some(panel), [Some(lh), Some(rh)]) = (both_on, hands) else {
+            self.scale = None;
+            return;
+        };
+        let hand_distance =
+            (position_of(lh.world_from_pointer) - position_of(rh.world_from_pointer)).magnitude();
+
+        match self.scale {
+            Some(scale) if scale.panel == panel => {
+                if scale.initial_hand_distance > f32::EPSILON {
+                    self.panels[panel].height = (scale.initial_height * hand_distance / scale.initial_hand_distance)
+                        .clamp(Panel::MIN_HEIGHT, Panel::MAX_HEIGHT);
+                }
+            }
+            _ => {
+                // Scaling is a grab, so neither hand pages on release
+                for state in self.hands.iter_mut() {
+                    if let HandState::Pressed { grabbing, .. } = state {
+                        *grabbing = true;
+                    }
+                }
+                self.scale = Some(TwoHandScale {
+                    panel,
+                    initial_hand_distance: hand_distance,
+                    initial_height: self.panels[panel].height,
+                });
+            }
+        }
+    }
+}
+
+fn position_of(world_from_local: Mat4) -> Vector3<f32> {
+    let [x, y, z, _] = world_from_local.0[3];
+    Vector3::new(x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    /// A panel 2m down -Z, 2m wide and 1m tall
+    fn panel() -> Panel {
+        Panel {
+            world_from_panel: Mat4::from_translation([0.0, 0.0, -2.0]),
+            height: 1.0,
+            aspect_ratio: 2.0,
+        }
+    }
+
+    /// A pointer at `[x, y, 0]` looking down -Z
+    fn pointer_at(x: f32, y: f32) -> Mat4 {
+        Mat4::from_translation([x, y, 0.0])
+    }
+
+    fn click(world_from_pointer: Mat4, click: bool) -> Option<HandPointer> {
+        Some(HandPointer { world_from_pointer, click })
+    }
+
+    /// `image_count` images, with [panel] in the first slot and every other panel out of reach
+    fn gallery(image_count: usize) -> Gallery {
+        let images = (0..image_count).map(|i| PathBuf::from(format!("{i}.png"))).collect();
+        let mut gallery = Gallery::new(images);
+        for panel in gallery.panels.iter_mut() {
+            panel.world_from_panel = Mat4::from_translation([0.0, 100.0, 0.0]);
+        }
+        gallery.panels[0] = panel();
+        gallery
+    }
+
+    #[test]
+    fn ray_hits_panel() {
+        let hit = panel().hit(pointer_at(0.0, 0.0)).unwrap();
+        assert!((hit.distance - 2.0).abs() < EPSILON);
+        assert!((hit.u - 0.5).abs() < EPSILON);
+        assert!((hit.v - 0.5).abs() < EPSILON);
+
+        let hit = panel().hit(pointer_at(0.9, -0.4)).unwrap();
+        assert!((hit.u - 0.95).abs() < EPSILON);
+        assert!((hit.v - 0.1).abs() < EPSILON);
+    }
+
+    #[test]
+    fn ray_misses_panel() {
+        // Past the right and top edges
+        assert!(panel().hit(pointer_at(1.1, 0.0)).is_none());
+        assert!(panel().hit(pointer_at(0.0, 0.6)).is_none());
+        // Pointing away from it
+        assert!(panel().hit(Mat4::from_rotation_y(std::f32::consts::PI)).is_none());
+        // Parallel to it
+        assert!(panel().hit(Mat4::from_rotation_y(std::f32::consts::FRAC_PI_2)).is_none());
+    }
+
+    #[test]
+    fn pages_wrap_at_either_end() {
+        let mut gallery = gallery(7);
+        assert_eq!(gallery.page_count(), 3);
+        gallery.previous_page();
+        assert_eq!(gallery.page(), 2);
+        gallery.next_page();
+        assert_eq!(gallery.page(), 0);
+    }
+
+    #[test]
+    fn last_page_has_empty_slots() {
+        let mut gallery = gallery(7);
+        gallery.previous_page();
+        let images = gallery.page_images();
+        assert_eq!(images.len(), Gallery::PANELS_PER_PAGE);
+        assert_eq!(images[0], Some(Path::new("6.png")));
+        assert_eq!(images[1..], [None::<&Path>, None]);
+    }
+
+    #[test]
+    fn no_images_is_one_placeholder_page() {
+        let mut gallery = Gallery::new(vec![]);
+        assert_eq!(gallery.page_count(), 1);
+        assert_eq!(gallery.page_images(), [None::<&Path>]);
+        gallery.next_page();
+        assert_eq!(gallery.page(), 0);
+    }
+
+    #[test]
+    fn click_pages_by_half() {
+        let mut gallery = gallery(7);
+        assert_eq!(gallery.update([click(pointer_at(0.5, 0.0), true), None], 0.0), [true, false]);
+        gallery.update([click(pointer_at(0.5, 0.0), false), None], 0.1);
+        assert_eq!(gallery.page(), 1);
+
+        gallery.update([None, click(pointer_at(-0.5, 0.0), true)], 1.0);
+        gallery.update([None, click(pointer_at(-0.5, 0.0), false)], 1.1);
+        assert_eq!(gallery.page(), 0);
+    }
+
+    #[test]
+    fn two_hands_scale_by_their_distance_ratio() {
+        let mut gallery = gallery(7);
+        let hands = |spread: f32, held: bool| [click(pointer_at(-spread, 0.0), held), click(pointer_at(spread, 0.0), held)];
+
+        assert_eq!(gallery.update(hands(0.5, true), 0.0), [true, true]);
+        gallery.update(hands(1.0, true), 0.1);
+        assert!((gallery.panels[0].height - 2.0).abs() < EPSILON);
+        gallery.update(hands(0.375, true), 0.2);
+        assert!((gallery.panels[0].height - 0.75).abs() < EPSILON);
+        gallery.update(hands(100.0, true), 0.3);
+        assert_eq!(gallery.panels[0].height, Panel::MAX_HEIGHT);
+
+        // Scaling is a grab, so letting go doesn't page
+        gallery.update(hands(100.0, false), 0.4);
+        assert_eq!(gallery.page(), 0);
+    }
+}
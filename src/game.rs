@@ -1,9 +1,8 @@
-use std::{marker::PhantomData, num::{NonZero, NonZeroU32}};
+use std::num::NonZeroU32;
 
-use crate::{controls::{Controls, PointAndClickControls}, math::{Mat4, Pose}, shell::XrShell, xr};
+use crate::{action_layers::ActionLayers, bindings::{BindingRegistry, BindingsFile}, buffers::{FrameArena, UniformRing}, gallery::{Gallery, HandPointer}, launch::LaunchArgs, mesh::{Mesh, MeshData, Vertex, VertexType}, model::Model, shader_bindings, texture::{TextureLoader, TextureMaterial, LINEAR_CLAMP}, shader::include_shader, controls::{Controls, HapticCommand, HapticOutput, PointAndClickControls, Vibration}, hud::Hud, math::{Mat4, Pose}, shell::{EyeViews, ReferenceSpaceChange, StageBounds, TrackingSpace, XrShell}, xr};
 
 use anyhow::Result;
-use wgpu::include_spirv_raw;
 
 #[derive(Debug, Clone, Copy)]
 struct TimeTracker {
@@ -61,15 +60,36 @@ impl Default for TimeTracker {
 }
 
 pub(crate) trait Game: Sized {
-    fn init(xr_shell: &XrShell) -> Result<Self>;
+    /// Push the game's action sets onto `action_layers` for [crate::App] to sync every frame
+    fn init(xr_shell: &XrShell, launch_args: &LaunchArgs, action_layers: &mut ActionLayers) -> Result<Self>;
 
     // Getter
     fn xr_stage(&self) -> &xr::Space;
 
-    /// Advance the game state to the predicted time
+    /// Advance the game state to the predicted time. Actions have already been synced.
     /// TODO pull TimeTracker out of this and into App, just push TimeDelta into tick_to
     fn tick_to(&mut self, xr_shell: &XrShell, predicted_display_time: xr::Time);
 
+    /// Draw onto the head-locked HUD after [Game::tick_to].
+    /// Called every frame even while the HUD is hidden, so games can toggle it from here.
+    /// By default draws the frame stats, session state and message log.
+    fn update_hud(&mut self, xr_shell: &XrShell, hud: &mut Hud) {
+        hud.draw_default(xr_shell);
+    }
+
+    /// Called when the runtime signals that a reference space's origin will change, e.g. the user recentered.
+    /// Frames before [ReferenceSpaceChange::change_time] still use the old origin.
+    fn on_reference_space_changed(&mut self, _xr_shell: &XrShell, _change: &ReferenceSpaceChange) -> Result<()> {
+        Ok(())
+    }
+
+    /// Desktop only: called between frames with the shader source files that changed on disk.
+    /// Games should rebuild the pipelines using them, and keep the old pipeline if that fails.
+    #[cfg(not(target_os = "android"))]
+    fn on_shaders_changed(&mut self, _xr_shell: &XrShell, _changed: &[std::path::PathBuf]) -> Result<()> {
+        Ok(())
+    }
+
     /// Record the command buffers for rendering, and return them for submission.
     /// Command buffers that don't depend on the view transforms can and should be submitted early, not returned.
     /// The command buffers that *are* returned will not be submitted immediately - [Game::load_view_transforms] will be called first.
@@ -81,7 +101,8 @@ pub(crate) trait Game: Sized {
     type CommandBuffers: IntoIterator<Item = wgpu::CommandBuffer>;
     fn prepare_render(&mut self, xr_shell: &XrShell, target_render_view: &wgpu::TextureView) -> Result<Self::CommandBuffers>;
 
-    fn load_view_transforms(&mut self, xr_shell: &XrShell, view_flags: xr::ViewStateFlags, views: &[xr::View]) -> Result<()>;
+    /// Upload the eye matrices for `eye_views`. These are exactly the views submitted to the compositor for this frame.
+    fn load_view_transforms(&mut self, xr_shell: &XrShell, eye_views: &EyeViews) -> Result<()>;
 }
 
 #[repr(C)]
@@ -89,7 +110,12 @@ pub(crate) trait Game: Sized {
 struct Eyes {
     eye_screen_from_world: [Mat4; 2],
 }
-const _: () = assert!(std::mem::size_of::<Eyes>() == 128);
+const _: () = {
+    use shader_bindings::scene::eyes;
+    assert!(std::mem::size_of::<Eyes>() == eyes::SIZE);
+    assert!(std::mem::offset_of!(Eyes, eye_screen_from_world) == eyes::EYE_SCREEN_FROM_WORLD);
+    assert!(std::mem::size_of::<[Mat4; 2]>() == eyes::EYE_SCREEN_FROM_WORLD_SIZE);
+};
 impl Default for Eyes {
     fn default() -> Self {
         Self { eye_screen_from_world: [Mat4::zero(); 2] }
@@ -101,90 +127,348 @@ impl Default for Eyes {
 struct PerObject {
     world_from_model: Mat4,
 }
-const _: () = assert!(std::mem::size_of::<PerObject>() == 64);
-
-struct UniformBuffer<T: bytemuck::Pod + bytemuck::Zeroable + Sized> {
-    buffer: wgpu::Buffer,
-    _t: PhantomData<T>,
+const _: () = {
+    use shader_bindings::scene::object;
+    assert!(std::mem::size_of::<PerObject>() == object::SIZE);
+    assert!(std::mem::offset_of!(PerObject, world_from_model) == object::WORLD_FROM_MODEL);
+    assert!(std::mem::size_of::<Mat4>() == object::WORLD_FROM_MODEL_SIZE);
+};
+
+/// The bind group shared by everything drawn with the quad pipeline:
+/// the eye matrices for the frame and a per-frame arena of [PerObject] data.
+/// Both are selected per draw with dynamic offsets, so objects don't need their own bind groups.
+struct SceneBindings {
+    eyes: UniformRing<Eyes>,
+    objects: FrameArena<PerObject>,
+    bind_group: wgpu::BindGroup,
 }
-impl<T: bytemuck::Pod + bytemuck::Zeroable + Sized> UniformBuffer<T> {
-    const _CHECK_SIZE: () = assert!(std::mem::size_of::<T>() > 0);
 
-    fn create(xr_shell: &XrShell) -> Self {
-        let buffer = xr_shell.wgpu_device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: std::mem::size_of::<T>() as u64,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-            mapped_at_creation: false,
-        });
-        Self { 
-            buffer,
-            _t: PhantomData::default(),
-        }
-    }
+impl SceneBindings {
+    /// Maximum number of objects drawn per frame
+    const MAX_OBJECTS: u64 = 4096;
 
-    fn overwrite(&self, xr_shell: &XrShell, value: &T) -> Result<()> {
-        match xr_shell.wgpu_queue.write_buffer_with(&self.buffer, 0, NonZero::new(std::mem::size_of::<T>() as u64).unwrap()) {
-            Some(mut buf) => {
-                let bytes = bytemuck::bytes_of(value);
-                buf.as_mut().copy_from_slice(bytes);
-                Ok(())
+    /// The shaders' layout, with dynamic offsets for both buffers
+    fn layout_entries() -> [wgpu::BindGroupLayoutEntry; 2] {
+        shader_bindings::scene::LAYOUT_ENTRIES.map(|mut entry| {
+            if let wgpu::BindingType::Buffer { has_dynamic_offset, .. } = &mut entry.ty {
+                *has_dynamic_offset = true;
             }
-            None => anyhow::bail!("Couldn't write uniform buffer"),
+            entry
+        })
+    }
+
+    fn new(xr_shell: &XrShell, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let eyes = UniformRing::new(xr_shell, wgpu::BufferUsages::UNIFORM, Some("eyes"));
+        let objects = FrameArena::new(xr_shell, Self::MAX_OBJECTS, wgpu::BufferUsages::UNIFORM, Some("per_object"));
+        let bind_group = xr_shell.wgpu_device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: shader_bindings::scene::eyes::BINDING,
+                    resource: eyes.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: shader_bindings::scene::object::BINDING,
+                    resource: objects.binding(),
+                },
+            ],
+        });
+        Self {
+            eyes,
+            objects,
+            bind_group,
         }
     }
 
-    fn buffer(&self) -> &wgpu::Buffer {
-        &self.buffer
+    /// Queue an object's uniforms for this frame and bind them for the next draw
+    fn bind_object(&mut self, xr_shell: &XrShell, render_pass: &mut wgpu::RenderPass, world_from_model: Mat4) -> Result<()> {
+        let object_offset = self.objects.push(xr_shell, &PerObject { world_from_model })?;
+        render_pass.set_bind_group(0, &self.bind_group, &[self.eyes.dynamic_offset(xr_shell), object_offset]);
+        Ok(())
     }
 }
 
-
 /// All meshes right now are rendered with the same shader, which hardcodes a quad
 struct Quad {
-    per_object_uniforms: UniformBuffer<PerObject>,
-    bindings: wgpu::BindGroup,
+    world_from_model: Mat4,
 }
 
 impl Quad {
-    fn new(xr_shell: &XrShell, bind_group_layout: &wgpu::BindGroupLayout, eye_uniform_buffer: &wgpu::Buffer) -> Self {
-        let per_object_uniforms = UniformBuffer::create(xr_shell);
+    fn new() -> Self {
         Self {
-            bindings: xr_shell.wgpu_device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                            buffer: eye_uniform_buffer,
-                            offset: 0,
-                            size: None,
-                        }),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                            buffer: per_object_uniforms.buffer(),
-                            offset: 0,
-                            size: None,
-                        }),
-                    },
-                ],
-            }),
-            per_object_uniforms,
+            world_from_model: Mat4::identity(),
         }
     }
 
-    fn update_uniforms(&self, xr_shell: &XrShell, world_from_model: Mat4) -> Result<()> {
-        self.per_object_uniforms.overwrite(xr_shell, &PerObject {
-            world_from_model
-        })
+    fn enqueue_draw(&self, xr_shell: &XrShell, render_pass: &mut wgpu::RenderPass, bindings: &mut SceneBindings) -> Result<()> {
+        bindings.bind_object(xr_shell, render_pass, self.world_from_model)?;
+        render_pass.draw(0..6, 0..1);
+        Ok(())
     }
+}
 
-    fn enqueue_draw(&self, render_pass: &mut wgpu::RenderPass) {
-        render_pass.set_bind_group(0, &self.bindings, &[]);
+/// A [Quad] showing a texture at its aspect ratio, drawn with the textured quad pipeline
+struct TexturedQuad {
+    /// The quad is 1 unit tall, and as wide as the texture's aspect ratio
+    world_from_model: Mat4,
+    material: TextureMaterial,
+}
+
+impl TexturedQuad {
+    fn new(material: TextureMaterial) -> Self {
+        Self {
+            world_from_model: Mat4::identity(),
+            material,
+        }
+    }
+
+    fn enqueue_draw(&self, xr_shell: &XrShell, render_pass: &mut wgpu::RenderPass, bindings: &mut SceneBindings) -> Result<()> {
+        let aspect_ratio = self.material.texture.aspect_ratio();
+        bindings.bind_object(xr_shell, render_pass, self.world_from_model * Mat4::scale_xyz([aspect_ratio, 1.0, 1.0]))?;
+        render_pass.set_bind_group(1, &self.material.bind_group, &[]);
         render_pass.draw(0..6, 0..1);
+        Ok(())
+    }
+}
+
+/// RGBA8 checkerboard with a colored border, to show when there's no image to display
+fn test_pattern_rgba8(width: u32, height: u32, cell_size: u32) -> Vec<u8> {
+    let border = cell_size / 4;
+    let mut rgba8 = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let texel = if x < border || y < border || x >= width - border || y >= height - border {
+                [230, 110, 30, 255]
+            } else if (x / cell_size + y / cell_size) % 2 == 0 {
+                [235, 235, 235, 255]
+            } else {
+                [40, 40, 40, 255]
+            };
+            rgba8.extend_from_slice(&texel);
+        }
+    }
+    rgba8
+}
+
+/// Depth attachment matching the swapchain, with one layer per eye
+struct DepthBuffer {
+    view: wgpu::TextureView,
+}
+
+impl DepthBuffer {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    fn new(xr_shell: &XrShell) -> Self {
+        let texture = xr_shell.wgpu_device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth"),
+            size: wgpu::Extent3d {
+                width: xr_shell.xr_swapchain.resolution.width,
+                height: xr_shell.xr_swapchain.resolution.height,
+                depth_or_array_layers: 2,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            array_layer_count: Some(2),
+            ..Default::default()
+        });
+        Self { view }
+    }
+
+    fn state() -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: Self::FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }
+    }
+}
+
+// Sources of the pipelines' shaders, relative to `src/`. Must match the `include_shader!` paths in [RectViewer::init]
+#[cfg(not(target_os = "android"))]
+const QUAD_SHADERS: [&str; 2] = ["shaders/wgsl/fullscreen.wgsl", "shaders/wgsl/debug_pattern.wgsl"];
+#[cfg(not(target_os = "android"))]
+const TEXTURED_QUAD_SHADER: &str = "shaders/wgsl/textured_quad.wgsl";
+#[cfg(not(target_os = "android"))]
+const MESH_SHADER: &str = "shaders/wgsl/mesh.wgsl";
+
+/// What differs between the scene's pipelines.
+/// They all render to both eyes of the sRGB swapchain with [DepthBuffer::state], see [create_scene_pipeline].
+struct ScenePipelineDesc<'a> {
+    label: &'a str,
+    layout: &'a wgpu::PipelineLayout,
+    vertex_shader: &'a wgpu::ShaderModule,
+    vertex_buffers: &'a [wgpu::VertexBufferLayout<'a>],
+    fragment_shader: &'a wgpu::ShaderModule,
+    topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+}
+
+fn create_scene_pipeline(xr_shell: &XrShell, desc: &ScenePipelineDesc) -> wgpu::RenderPipeline {
+    xr_shell
+        .wgpu_device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            cache: xr_shell.pipeline_cache(),
+            label: Some(desc.label),
+            layout: Some(desc.layout),
+            vertex: wgpu::VertexState {
+                module: desc.vertex_shader,
+                entry_point: "vs_main",
+                buffers: desc.vertex_buffers,
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: desc.topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: desc.cull_mode,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthBuffer::state()),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0x0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: desc.fragment_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::RED
+                        | wgpu::ColorWrites::GREEN
+                        | wgpu::ColorWrites::BLUE,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // Render to both eyes in multipass
+            multiview: Some(NonZeroU32::new(2).unwrap()),
+        })
+}
+
+/// Create a pipeline for drawing [Mesh]es of [Vertex] with the given topology using `mesh.wgsl`
+fn create_mesh_pipeline(
+    xr_shell: &XrShell,
+    pipeline_layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    topology: wgpu::PrimitiveTopology,
+) -> wgpu::RenderPipeline {
+    let vertex_layout = Vertex::layout();
+    create_scene_pipeline(xr_shell, &ScenePipelineDesc {
+        label: "mesh",
+        layout: pipeline_layout,
+        vertex_shader: shader,
+        vertex_buffers: &[vertex_layout.buffer_layout()],
+        fragment_shader: shader,
+        topology,
+        cull_mode: match topology {
+            wgpu::PrimitiveTopology::TriangleList
+            | wgpu::PrimitiveTopology::TriangleStrip => Some(wgpu::Face::Back),
+            _ => None,
+        },
+    })
+}
+
+/// Create a pipeline for a fullscreen-style quad with no vertex buffers, e.g. `fullscreen.wgsl` and `debug_pattern.wgsl`
+fn create_quad_pipeline(
+    xr_shell: &XrShell,
+    pipeline_layout: &wgpu::PipelineLayout,
+    vertex_shader: &wgpu::ShaderModule,
+    fragment_shader: &wgpu::ShaderModule,
+) -> wgpu::RenderPipeline {
+    create_scene_pipeline(xr_shell, &ScenePipelineDesc {
+        label: "quad",
+        layout: pipeline_layout,
+        vertex_shader,
+        vertex_buffers: &[],
+        fragment_shader,
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        cull_mode: None,
+    })
+}
+
+/// Create a pipeline for [TexturedQuad]s using `textured_quad.wgsl`
+fn create_textured_quad_pipeline(
+    xr_shell: &XrShell,
+    pipeline_layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+) -> wgpu::RenderPipeline {
+    create_scene_pipeline(xr_shell, &ScenePipelineDesc {
+        label: "textured_quad",
+        layout: pipeline_layout,
+        vertex_shader: shader,
+        vertex_buffers: &[],
+        fragment_shader: shader,
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        cull_mode: None,
+    })
+}
+
+/// Debug renderer for the play area, drawn as four thin strips lying on the floor
+struct StageBoundsOutline {
+    edges: [Quad; 4],
+    visible: bool,
+}
+
+impl StageBoundsOutline {
+    /// Width of each strip in meters
+    const THICKNESS: f32 = 0.03;
+    /// Lift the strips slightly off the floor so they aren't hidden by floor-level content
+    const HEIGHT: f32 = 0.005;
+
+    fn new() -> Self {
+        Self {
+            edges: [Quad::new(), Quad::new(), Quad::new(), Quad::new()],
+            visible: false,
+        }
+    }
+
+    /// Place the edges around the bounds, or hide them if there are none.
+    /// `world_from_stage` maps the STAGE-space bounds into the space the game renders in.
+    fn update(&mut self, stage_bounds: Option<(StageBounds, Mat4)>) {
+        let Some((bounds, world_from_stage)) = stage_bounds else {
+            self.visible = false;
+            return;
+        };
+        self.visible = true;
+
+        // Quads are drawn in the XY plane, so rotate them to lie flat in XZ.
+        // After this rotation, the quad's local +Y points along -Z.
+        let flat = Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2);
+        let (half_width, half_depth) = (bounds.width / 2.0, bounds.depth / 2.0);
+        let along_x = Mat4::scale_xyz([bounds.width + Self::THICKNESS, Self::THICKNESS, 1.0]);
+        let along_z = Mat4::scale_xyz([Self::THICKNESS, bounds.depth + Self::THICKNESS, 1.0]);
+
+        let stage_from_edges = [
+            Mat4::from_translation([0.0, Self::HEIGHT, -half_depth]) * flat * along_x,
+            Mat4::from_translation([0.0, Self::HEIGHT, half_depth]) * flat * along_x,
+            Mat4::from_translation([-half_width, Self::HEIGHT, 0.0]) * flat * along_z,
+            Mat4::from_translation([half_width, Self::HEIGHT, 0.0]) * flat * along_z,
+        ];
+        for (edge, stage_from_edge) in self.edges.iter_mut().zip(stage_from_edges) {
+            edge.world_from_model = world_from_stage * stage_from_edge;
+        }
+    }
+
+    fn enqueue_draw(&self, xr_shell: &XrShell, render_pass: &mut wgpu::RenderPass, bindings: &mut SceneBindings) -> Result<()> {
+        if self.visible {
+            for edge in self.edges.iter() {
+                edge.enqueue_draw(xr_shell, render_pass, bindings)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -192,41 +476,142 @@ pub(crate) struct RectViewer {
     time: TimeTracker,
     delta_real_time: f32,
 
-    xr_stage: xr::Space,
+    tracking_space: TrackingSpace,
 
     controls: PointAndClickControls,
+    hud_toggle_requested: bool,
+    /// The menu button has been held long enough to recenter, so its release won't toggle the HUD
+    menu_long_pressed: bool,
     
+    /// Scene bindings only, for the quad pipeline
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Scene bindings then a [TextureMaterial], shared by the textured quad and mesh pipelines
+    material_pipeline_layout: wgpu::PipelineLayout,
     wgpu_render_pipeline: wgpu::RenderPipeline,
-    eye_uniform_buffer: UniformBuffer<Eyes>,
-    meshes: [Quad; 3],
+    textured_quad_pipeline: wgpu::RenderPipeline,
+    mesh_pipeline: wgpu::RenderPipeline,
+    line_pipeline: wgpu::RenderPipeline,
+    depth_buffer: DepthBuffer,
+    bindings: SceneBindings,
+    textures: TextureLoader,
+    /// 1x1 white, for meshes without a texture of their own
+    white_material: TextureMaterial,
+    gallery: Gallery,
+    /// One per [Gallery::panels], showing the images of [RectViewer::loaded_page]
+    panel_quads: Vec<TexturedQuad>,
+    loaded_page: Option<usize>,
+    stage_outline: StageBoundsOutline,
+
+    floor_grid: Mesh,
+    hand_cube: Mesh,
+    /// Grip poses of the left and right hands, if tracked
+    hand_cube_transforms: [Option<Mat4>; 2],
+    /// A 1m line down -Z
+    pointer_ray: Mesh,
+    /// Left and right pointer rays, scaled to end where they hit a panel
+    pointer_ray_transforms: [Option<Mat4>; 2],
+
+    /// glTF models and where they are placed in the stage
+    models: Vec<(Model, Mat4)>,
+}
+impl RectViewer {
+    /// Environment variable naming a `.gltf` or `.glb` to show in front of the user on desktop
+    #[cfg(not(target_os = "android"))]
+    const MODEL_PATH_VAR: &'static str = "RECT_VIEWER_GLTF";
+    /// Environment variable naming a [BindingsFile] to use instead of [PointAndClickControls::DEFAULT_BINDINGS] on desktop
+    #[cfg(not(target_os = "android"))]
+    const BINDINGS_PATH_VAR: &'static str = "RECT_VIEWER_BINDINGS";
+
+    fn load_bindings() -> Result<BindingsFile> {
+        #[cfg(not(target_os = "android"))]
+        if let Ok(path) = std::env::var(Self::BINDINGS_PATH_VAR) {
+            return BindingsFile::load(std::path::Path::new(&path));
+        }
+        BindingsFile::parse(PointAndClickControls::DEFAULT_BINDINGS, "point_and_click.ron")
+    }
+
+    /// How far the pointer rays reach when they don't hit a panel
+    const POINTER_RAY_LENGTH: f32 = 3.0;
+
+    pub fn place_model(&mut self, model: Model, world_from_model: Mat4) {
+        self.models.push((model, world_from_model));
+    }
+
+    fn placeholder_material(&mut self, xr_shell: &XrShell) -> TextureMaterial {
+        let test_pattern = self.textures.from_rgba8(
+            xr_shell,
+            512,
+            384,
+            &test_pattern_rgba8(512, 384, 32),
+            true,
+            Some("test_pattern"),
+        );
+        self.textures.create_material(xr_shell, test_pattern, &LINEAR_CLAMP)
+    }
+
+    /// Load the images for the gallery's current page onto the panels, and resize the panels to fit them.
+    /// Images which fail to load are shown as the test pattern.
+    fn load_page(&mut self, xr_shell: &XrShell) {
+        let page_images: Vec<Option<std::path::PathBuf>> = self
+            .gallery
+            .page_images()
+            .into_iter()
+            .map(|path| path.map(Into::into))
+            .collect();
+        // Drop the previous page's textures before loading the next
+        self.panel_quads.clear();
+        for (slot, path) in page_images.iter().enumerate() {
+            let texture = path.as_ref().and_then(|path| {
+                self.textures
+                    .load(xr_shell, path, true)
+                    .map_err(|e| log::warn!("Failed to load {}: {e:?}", path.display()))
+                    .ok()
+            });
+            let material = match texture {
+                Some(texture) => self.textures.create_material(xr_shell, texture, &LINEAR_CLAMP),
+                None => self.placeholder_material(xr_shell),
+            };
+            self.gallery.panels[slot].aspect_ratio = material.texture.aspect_ratio();
+            self.panel_quads.push(TexturedQuad::new(material));
+        }
+        self.loaded_page = Some(self.gallery.page());
+    }
+
+    fn enqueue_model_draws(
+        &mut self,
+        xr_shell: &XrShell,
+        render_pass: &mut wgpu::RenderPass,
+    ) -> Result<()> {
+        // One pass per pipeline, rather than switching pipelines between primitives
+        for (pipeline, topology) in [
+            (&self.mesh_pipeline, wgpu::PrimitiveTopology::TriangleList),
+            (&self.line_pipeline, wgpu::PrimitiveTopology::LineList),
+        ] {
+            render_pass.set_pipeline(pipeline);
+            for (model, world_from_model) in self.models.iter() {
+                for (world_from_node, primitive) in model.draws(*world_from_model) {
+                    if primitive.mesh.topology() == topology {
+                        self.bindings.bind_object(xr_shell, render_pass, world_from_node)?;
+                        let material = model
+                            .base_color_bind_group(primitive)
+                            .unwrap_or(&self.white_material.bind_group);
+                        render_pass.set_bind_group(1, material, &[]);
+                        primitive.mesh.enqueue_draw(render_pass);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 impl Game for RectViewer {
-    fn init(xr_shell: &XrShell) -> Result<Self> {
-        let vertex_shader = unsafe {
-            xr_shell.wgpu_device
-                .create_shader_module_spirv(&include_spirv_raw!("shaders/spv/fullscreen.vert.spv"))
-        };
-        let fragment_shader = unsafe {
-            xr_shell.wgpu_device
-                .create_shader_module_spirv(&include_spirv_raw!("shaders/spv/debug_pattern.frag.spv"))
-        };
+    fn init(xr_shell: &XrShell, launch_args: &LaunchArgs, action_layers: &mut ActionLayers) -> Result<Self> {
+        let vertex_shader = include_shader!(xr_shell, "shaders/wgsl/fullscreen.wgsl")?;
+        let fragment_shader = include_shader!(xr_shell, "shaders/wgsl/debug_pattern.wgsl")?;
 
         let bind_group_layout = xr_shell.wgpu_device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                    count: None,
-                },
-            ],
+            entries: &SceneBindings::layout_entries(),
         });
 
         let pipeline_layout =
@@ -241,99 +626,131 @@ impl Game for RectViewer {
                 });
 
         let wgpu_render_pipeline =
+            create_quad_pipeline(xr_shell, &pipeline_layout, &vertex_shader, &fragment_shader);
+
+        let mut textures = TextureLoader::new(xr_shell)?;
+        let material_pipeline_layout =
             xr_shell
                 .wgpu_device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    cache: None, // TODO caching
-                    label: None,
-                    layout: Some(&pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &vertex_shader,
-                        entry_point: "main",
-                        buffers: &[],
-                        compilation_options: Default::default(),
-                    },
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: None,
-                        unclipped_depth: false,
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        conservative: false,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState {
-                        count: 1,
-                        mask: !0x0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &fragment_shader,
-                        entry_point: "main",
-                        targets: &[Some(wgpu::ColorTargetState {
-                            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                            blend: None,
-                            write_mask: wgpu::ColorWrites::RED
-                                | wgpu::ColorWrites::GREEN
-                                | wgpu::ColorWrites::BLUE,
-                        })],
-                        compilation_options: Default::default(),
-                    }),
-                    // Render to both eyes in multipass
-                    multiview: Some(NonZeroU32::new(2).unwrap()),
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("material"),
+                    bind_group_layouts: &[
+                        &bind_group_layout,
+                        textures.material_layout(),
+                    ],
+                    push_constant_ranges: &[],
                 });
+        let white = textures.from_rgba8(xr_shell, 1, 1, &[255; 4], true, Some("white"));
+        let white_material = textures.create_material(xr_shell, white, &LINEAR_CLAMP);
+
+        let mesh_shader = include_shader!(xr_shell, "shaders/wgsl/mesh.wgsl")?;
+        let mesh_pipeline = create_mesh_pipeline(
+            xr_shell,
+            &material_pipeline_layout,
+            &mesh_shader,
+            wgpu::PrimitiveTopology::TriangleList,
+        );
+        let line_pipeline = create_mesh_pipeline(
+            xr_shell,
+            &material_pipeline_layout,
+            &mesh_shader,
+            wgpu::PrimitiveTopology::LineList,
+        );
+
+        let depth_buffer = DepthBuffer::new(xr_shell);
+        let bindings = SceneBindings::new(xr_shell, &bind_group_layout);
+
+        let floor_grid = MeshData::grid(4.0, 8)
+            .with_color([0.6, 0.6, 0.6, 1.0])
+            .upload(xr_shell, Some("floor_grid"));
+        let hand_cube = MeshData::cube(0.05)
+            .with_color([0.9, 0.4, 0.1, 1.0])
+            .upload(xr_shell, Some("hand_cube"));
+
+        let pointer_ray = MeshData {
+            vertices: vec![
+                Vertex::new([0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0]),
+                Vertex::new([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0]),
+            ],
+            indices: vec![0, 1],
+            topology: wgpu::PrimitiveTopology::LineList,
+        }
+        .with_color([1.0, 1.0, 1.0, 1.0])
+        .upload(xr_shell, Some("pointer_ray"));
+
+        let gallery = match &launch_args.image_dir {
+            Some(dir) => Gallery::from_dir(dir)?,
+            None => {
+                log::info!("No image directory given, showing a test pattern");
+                Gallery::new(vec![])
+            }
+        };
 
-        let eye_uniform_buffer = UniformBuffer::create(xr_shell);
-
-        let meshes = [
-            Quad::new(xr_shell, &bind_group_layout, eye_uniform_buffer.buffer()),
-            Quad::new(xr_shell, &bind_group_layout, eye_uniform_buffer.buffer()),
-            Quad::new(xr_shell, &bind_group_layout, eye_uniform_buffer.buffer()),
-        ];
-        meshes[0].update_uniforms(xr_shell, Mat4::from_translation([0.0, 0.0, -2.0]))?;
-
-        let controls = PointAndClickControls::new(
-            xr_shell, "point_and_click", "Point & Click"
-        )?;
+        let textured_quad_shader = include_shader!(xr_shell, "shaders/wgsl/textured_quad.wgsl")?;
+        let textured_quad_pipeline =
+            create_textured_quad_pipeline(xr_shell, &material_pipeline_layout, &textured_quad_shader);
+        let stage_outline = StageBoundsOutline::new();
 
-        // Bind our actions to input devices using the given profile
-        // If you want to access inputs specific to a particular device you may specify a different
-        // interaction profile
-        for interaction_binding in controls.suggested_bindings(&xr_shell.xr_instance)? {
-            xr_shell
-                .xr_instance
-                .suggest_interaction_profile_bindings(
-                    xr_shell
-                        .xr_instance
-                        .string_to_path(interaction_binding.0)?,
-                    &interaction_binding.1
-                )?;
-        }
+        let bindings_file = Self::load_bindings()?;
+        let controls_decl = bindings_file.action_set("point_and_click")?;
+        let controls = PointAndClickControls::new(xr_shell, controls_decl)?;
 
-        // Attach the action set to the session
-        xr_shell
-            .xr_session
-            .attach_action_sets(&[&controls.action_set()])?;
+        // Bind our actions to input devices using each profile the scheme supports,
+        // and attach its action set to the session.
+        // Other schemes' bindings would be added to the same registry, as each profile can only be
+        // suggested once and action sets can only be attached once.
+        let mut bindings = BindingRegistry::new();
+        bindings.add(&xr_shell.xr_instance, &controls)?;
+        bindings.suggest_and_attach(xr_shell)?;
+        action_layers.push("point_and_click", controls.action_set(), controls_decl.priority);
 
         // OpenXR uses a couple different types of reference frames for positioning content; we need
         // to choose one for displaying our content! STAGE would be relative to the center of your
         // guardian system's bounds, and LOCAL would be relative to your device's starting location.
-        let xr_stage = xr_shell
-            .xr_session
-            .create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)?;
+        // Prefer STAGE, but fall back to something floor-relative if there's no play area.
+        let tracking_space = xr_shell.create_tracking_space(XrShell::DEFAULT_TRACKING_SPACE_PREFERENCES)?;
 
-        Ok(Self {
+        let mut viewer = Self {
             time: Default::default(),
             delta_real_time: 0.0,
 
             controls,
-            xr_stage,
+            hud_toggle_requested: false,
+            menu_long_pressed: false,
+            tracking_space,
         
+            pipeline_layout,
+            material_pipeline_layout,
             wgpu_render_pipeline,
-            eye_uniform_buffer,
-            meshes,
-        })
+            textured_quad_pipeline,
+            mesh_pipeline,
+            line_pipeline,
+            depth_buffer,
+            bindings,
+            textures,
+            white_material,
+            gallery,
+            panel_quads: vec![],
+            loaded_page: None,
+            stage_outline,
+
+            floor_grid,
+            hand_cube,
+            hand_cube_transforms: [None, None],
+            pointer_ray,
+            pointer_ray_transforms: [None, None],
+
+            models: vec![],
+        };
+        viewer.load_page(xr_shell);
+
+        #[cfg(not(target_os = "android"))]
+        if let Ok(path) = std::env::var(Self::MODEL_PATH_VAR) {
+            let model = Model::load(xr_shell, &mut viewer.textures, &path)?;
+            viewer.place_model(model, Mat4::from_translation([0.0, 0.0, -1.0]));
+        }
+
+        Ok(viewer)
     }
 
     fn tick_to(&mut self, xr_shell: &XrShell, predicted_display_time: openxr::Time) {
@@ -344,25 +761,142 @@ impl Game for RectViewer {
         };
         log::info!("delta_time: {}", self.delta_real_time);
 
-        xr_shell
-            .xr_session
-            .sync_actions(&[self.controls.action_set().into()])
-            .unwrap();
+        if let Err(e) = self.tracking_space.update(xr_shell, predicted_display_time) {
+            log::warn!("Failed to reset the tracking space: {e:?}");
+        }
+
+        // Find where our controllers are located in the tracking space
+        let inputs = self.controls.locate(xr_shell, &self.tracking_space.space, predicted_display_time).unwrap();
+
+        // Holding the menu button recenters, and a shorter press toggles the HUD when it's let go
+        if inputs.menu_button.long_press {
+            self.menu_long_pressed = true;
+            if let Err(e) = self.tracking_space.recenter(xr_shell, predicted_display_time) {
+                log::warn!("Failed to recenter: {e:?}");
+            }
+        }
+        if inputs.menu_button.released {
+            self.hud_toggle_requested = !self.menu_long_pressed;
+            self.menu_long_pressed = false;
+        }
+
+        let pointers = [&inputs.lh, &inputs.rh].map(|hand| {
+            hand.as_ref().map(|hand| HandPointer {
+                world_from_pointer: hand.point.posed_from_local(),
+                click: hand.click.held,
+            })
+        });
+        let landed = self.gallery.update(pointers, self.time.real_time_secs());
+        // Pulse the controller whose click landed on a panel
+        let [lh, rh] = landed.map(|landed| {
+            if landed {
+                HapticCommand::Vibrate(Vibration::CLICK)
+            } else {
+                HapticCommand::None
+            }
+        });
+        if let Err(e) = self.controls.apply(xr_shell, &HapticOutput { lh, rh }) {
+            log::warn!("Failed to apply haptics: {e:?}");
+        }
+        if self.loaded_page != Some(self.gallery.page()) {
+            self.load_page(xr_shell);
+        }
+        for (quad, panel) in self.panel_quads.iter_mut().zip(self.gallery.panels.iter()) {
+            quad.world_from_model = panel.world_from_panel * Mat4::scale(panel.height);
+        }
+
+        self.pointer_ray_transforms = pointers.map(|pointer| {
+            pointer.map(|pointer| {
+                let length = self
+                    .gallery
+                    .pointer_hit(pointer.world_from_pointer)
+                    .map_or(Self::POINTER_RAY_LENGTH, |(_, hit)| hit.distance);
+                pointer.world_from_pointer * Mat4::scale(length)
+            })
+        });
+
+        self.hand_cube_transforms = [
+            inputs.lh.as_ref().map(|lh| lh.grip.posed_from_local()),
+            inputs.rh.as_ref().map(|rh| rh.grip.posed_from_local()),
+        ];
+
+        // The bounds are given in STAGE space, which may not be what we're tracking in, or may have been recentered
+        let stage_bounds = xr_shell
+            .locate_stage_bounds(&self.tracking_space.space, predicted_display_time)
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to locate the stage bounds: {e:?}");
+                None
+            });
+        self.stage_outline.update(stage_bounds);
+    }
 
-        // Find where our controllers are located in the Stage space
-        let inputs = self.controls.locate(xr_shell, &self.xr_stage, predicted_display_time).unwrap();
+    fn update_hud(&mut self, xr_shell: &XrShell, hud: &mut Hud) {
+        if self.hud_toggle_requested {
+            hud.toggle();
+            self.hud_toggle_requested = false;
+        }
+        hud.draw_default(xr_shell);
+        hud.line(&format!("tracking {:?}", self.tracking_space.kind));
+        hud.line(&format!("page {}/{}", self.gallery.page() + 1, self.gallery.page_count()));
+    }
 
-        if let Some(lh) = inputs.lh {
-            self.meshes[1].update_uniforms(xr_shell, lh.point.posed_from_local() * Mat4::scale(0.1)).unwrap();
+    fn on_reference_space_changed(&mut self, _xr_shell: &XrShell, change: &ReferenceSpaceChange) -> Result<()> {
+        // The runtime has recentered the space under ours, so drop our own recenter when that lands
+        if change.space_type == self.tracking_space.reference_space_type() {
+            self.tracking_space.reset_at(change.change_time);
         }
+        Ok(())
+    }
 
-        if let Some(rh) = inputs.rh {
-            self.meshes[2].update_uniforms(xr_shell, rh.point.posed_from_local() * Mat4::scale(0.1)).unwrap();
+    #[cfg(not(target_os = "android"))]
+    fn on_shaders_changed(&mut self, xr_shell: &XrShell, changed: &[std::path::PathBuf]) -> Result<()> {
+        use crate::{hot_reload, shader::load_shader_module};
+
+        let uses = |sources: &[&str]| sources.iter().any(|source| changed.iter().any(|c| c.ends_with(source)));
+        let load = |source: &str| load_shader_module(xr_shell, hot_reload::source_path(source));
+
+        // A failed rebuild is logged and the old pipeline kept, so a typo doesn't take the scene down
+        if uses(&QUAD_SHADERS) {
+            match hot_reload::create_validated(xr_shell, || {
+                let [vertex, fragment] = QUAD_SHADERS;
+                Ok(create_quad_pipeline(xr_shell, &self.pipeline_layout, &load(vertex)?, &load(fragment)?))
+            }) {
+                Ok(pipeline) => self.wgpu_render_pipeline = pipeline,
+                Err(e) => log::error!("Keeping the old quad pipeline: {e:#}"),
+            }
+        }
+        if uses(&[TEXTURED_QUAD_SHADER]) {
+            match hot_reload::create_validated(xr_shell, || {
+                Ok(create_textured_quad_pipeline(
+                    xr_shell,
+                    &self.material_pipeline_layout,
+                    &load(TEXTURED_QUAD_SHADER)?,
+                ))
+            }) {
+                Ok(pipeline) => self.textured_quad_pipeline = pipeline,
+                Err(e) => log::error!("Keeping the old textured quad pipeline: {e:#}"),
+            }
+        }
+        if uses(&[MESH_SHADER]) {
+            match hot_reload::create_validated(xr_shell, || {
+                let shader = load(MESH_SHADER)?;
+                Ok([wgpu::PrimitiveTopology::TriangleList, wgpu::PrimitiveTopology::LineList]
+                    .map(|topology| create_mesh_pipeline(xr_shell, &self.material_pipeline_layout, &shader, topology)))
+            }) {
+                Ok([mesh, line]) => {
+                    self.mesh_pipeline = mesh;
+                    self.line_pipeline = line;
+                }
+                Err(e) => log::error!("Keeping the old mesh pipelines: {e:#}"),
+            }
         }
+        Ok(())
     }
 
     type CommandBuffers = [wgpu::CommandBuffer; 1];
     fn prepare_render(&mut self, xr_shell: &XrShell, target_render_view: &wgpu::TextureView) -> Result<Self::CommandBuffers> {
+        self.bindings.objects.clear();
+
         let mut command_encoder = xr_shell
             .wgpu_device
             .create_command_encoder(&Default::default());
@@ -383,7 +917,14 @@ impl Game for RectViewer {
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_buffer.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
@@ -404,26 +945,45 @@ impl Game for RectViewer {
             );
 
             render_pass.set_pipeline(&self.wgpu_render_pipeline);
-            for quad in self.meshes.iter() {
-                quad.enqueue_draw(&mut render_pass);
+            self.stage_outline.enqueue_draw(xr_shell, &mut render_pass, &mut self.bindings)?;
+
+            render_pass.set_pipeline(&self.textured_quad_pipeline);
+            for quad in self.panel_quads.iter() {
+                quad.enqueue_draw(xr_shell, &mut render_pass, &mut self.bindings)?;
+            }
+
+            render_pass.set_pipeline(&self.mesh_pipeline);
+            render_pass.set_bind_group(1, &self.white_material.bind_group, &[]);
+            for world_from_model in self.hand_cube_transforms.iter().flatten() {
+                self.bindings.bind_object(xr_shell, &mut render_pass, *world_from_model)?;
+                self.hand_cube.enqueue_draw(&mut render_pass);
             }
+
+            render_pass.set_pipeline(&self.line_pipeline);
+            self.bindings.bind_object(xr_shell, &mut render_pass, Mat4::identity())?;
+            self.floor_grid.enqueue_draw(&mut render_pass);
+            for world_from_model in self.pointer_ray_transforms.iter().flatten() {
+                self.bindings.bind_object(xr_shell, &mut render_pass, *world_from_model)?;
+                self.pointer_ray.enqueue_draw(&mut render_pass);
+            }
+
+            self.enqueue_model_draws(xr_shell, &mut render_pass)?;
         }
+        // Upload the per-object data for everything drawn above.
+        // Queue writes happen before the command buffer is submitted.
+        self.bindings.objects.flush(xr_shell);
 
         Ok([command_encoder.finish()])
     }
 
-    fn load_view_transforms(&mut self, xr_shell: &XrShell, _view_flags: xr::ViewStateFlags, views: &[xr::View]) -> Result<()> {
+    fn load_view_transforms(&mut self, xr_shell: &XrShell, eye_views: &EyeViews) -> Result<()> {
         // Load the views into a uniform buffer
 
         const NEAR_Z: f32 = 0.01;
         const FAR_Z: f32 = 50.0;
 
         let mut matrices = Eyes::default();
-        for (i, view) in views.iter().enumerate() {
-            if i >= 2 {
-                continue;
-            }
-
+        for (i, view) in eye_views.views.iter().enumerate() {
             let screen_from_view = Mat4::xr_projection_fov(view.fov, NEAR_Z, FAR_Z);
 
             // view.pose() is the position and orientation of the view in world space.
@@ -435,10 +995,11 @@ impl Game for RectViewer {
             matrices.eye_screen_from_world[i] = screen_from_view * view_from_world;
         }
 
-        self.eye_uniform_buffer.overwrite(xr_shell, &matrices)
+        self.bindings.eyes.write(xr_shell, &matrices);
+        Ok(())
     }
 
     fn xr_stage<'a>(&'a self) -> &'a openxr::Space {
-        &self.xr_stage
+        &self.tracking_space.space
     }
 }
\ No newline at end of file
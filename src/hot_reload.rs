@@ -0,0 +1,79 @@
+//! Desktop-only shader hot reloading.
+//!
+//! [ShaderWatcher] watches `src/shaders/` in the source tree the app was built from, and reports
+//! which files changed since the last poll. The [crate::game::Game] decides which pipelines those
+//! files affect, and rebuilds them with [create_validated] so a broken shader leaves the old pipeline in place.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use anyhow::{Context, Result};
+
+use crate::shell::XrShell;
+
+/// The `src/` directory of the crate, which `include_shader!` paths are relative to
+pub fn source_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src"))
+}
+
+/// Where an `include_shader!` path lives on disk
+pub fn source_path(include_path: &str) -> PathBuf {
+    source_dir().join(include_path)
+}
+
+pub struct ShaderWatcher {
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Result<Self> {
+        let dir = source_dir().join("shaders");
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away when the watcher is dropped
+            let _ = sender.send(event);
+        })?;
+        notify::Watcher::watch(&mut watcher, &dir, notify::RecursiveMode::Recursive)
+            .with_context(|| format!("Watching {}", dir.display()))?;
+        log::info!("Watching {} for shader changes", dir.display());
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Files created or modified since the last call, without blocking. Each file is listed once.
+    pub fn poll_changed(&self) -> Vec<PathBuf> {
+        let mut changed = BTreeSet::new();
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+                    changed.extend(event.paths.into_iter().filter(|path| path.is_file()));
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Shader watcher error: {e}"),
+            }
+        }
+        changed.into_iter().collect()
+    }
+}
+
+/// Run `create` (e.g. pipeline creation) and return wgpu's validation error instead of panicking on it
+pub fn create_validated<T>(xr_shell: &XrShell, create: impl FnOnce() -> Result<T>) -> Result<T> {
+    xr_shell
+        .wgpu_device
+        .push_error_scope(wgpu::ErrorFilter::Validation);
+    let result = create();
+    // Native wgpu resolves error scopes immediately
+    let error = pollster::block_on(xr_shell.wgpu_device.pop_error_scope());
+    let value = result?;
+    match error {
+        Some(error) => Err(anyhow::anyhow!("{error}")),
+        None => Ok(value),
+    }
+}
@@ -0,0 +1,455 @@
+//! Head-locked heads-up display.
+//!
+//! The HUD is drawn on the CPU into a small RGBA canvas with a built-in 5x7 bitmap font,
+//! uploaded into its own single-layer swapchain, and composited by the runtime as a
+//! [xr::CompositionLayerQuad] in the VIEW reference space.
+//! Because it's a separate layer, the runtime keeps it locked to the head even if the
+//! projection layer is reprojected, and it never has to go through the game's render pipeline.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use anyhow::Result;
+
+use crate::shell::{Swapchain, XrShell};
+use crate::xr;
+
+pub type HudColor = [u8; 4];
+
+pub const HUD_BACKGROUND: HudColor = [0, 0, 0, 160];
+pub const HUD_TEXT: HudColor = [255, 255, 255, 255];
+pub const HUD_HIGHLIGHT: HudColor = [255, 220, 64, 255];
+
+pub struct HudConfig {
+    /// Distance in meters in front of the eyes
+    pub distance: f32,
+    /// Width of the quad in meters. The height is derived from the resolution.
+    pub width: f32,
+    /// Offset in meters along +Y, i.e. negative values place the HUD below the line of sight
+    pub vertical_offset: f32,
+    /// Resolution of the backing swapchain in texels
+    pub resolution: (u32, u32),
+    /// Number of messages kept by [Hud::push_message]
+    pub max_messages: usize,
+    /// Whether the HUD starts out shown. [Hud::set_visible] and [Hud::toggle] change it from then on.
+    pub visible: bool,
+}
+impl Default for HudConfig {
+    fn default() -> Self {
+        Self {
+            distance: 1.0,
+            width: 0.6,
+            vertical_offset: -0.15,
+            resolution: (640, 320),
+            max_messages: 8,
+            visible: false,
+        }
+    }
+}
+
+/// Frame timing, updated by the app once per frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    last_frame: Option<Instant>,
+    pub frame_count: u64,
+    /// Smoothed frames per second, measured on the CPU
+    pub fps: f32,
+    /// Smoothed CPU time between frames in milliseconds
+    pub cpu_frame_ms: f32,
+    /// The runtime's predicted display period in milliseconds
+    pub display_period_ms: f32,
+}
+impl FrameStats {
+    /// Weight of the newest sample in the exponential moving averages
+    const SMOOTHING: f32 = 0.1;
+
+    pub fn record_frame(&mut self, predicted_display_period: xr::Duration) {
+        let now = Instant::now();
+        self.frame_count += 1;
+        self.display_period_ms = (predicted_display_period.as_nanos() as f32) / 1e6;
+
+        if let Some(last_frame) = self.last_frame {
+            let frame_ms = now.duration_since(last_frame).as_secs_f32() * 1e3;
+            if self.cpu_frame_ms == 0.0 {
+                self.cpu_frame_ms = frame_ms;
+            } else {
+                self.cpu_frame_ms += (frame_ms - self.cpu_frame_ms) * Self::SMOOTHING;
+            }
+            if self.cpu_frame_ms > 0.0 {
+                self.fps = 1e3 / self.cpu_frame_ms;
+            }
+        }
+        self.last_frame = Some(now);
+    }
+}
+
+/// Each glyph is 5 columns of 7 bits (LSB = top row), for ASCII 0x20..=0x7E.
+const FONT_5X7: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x02, 0x01, 0x02, 0x04, 0x02], // ~
+];
+
+/// CPU-side RGBA8 image that text is rasterized into
+struct HudCanvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+impl HudCanvas {
+    /// Each glyph is drawn at 2x scale into a 6x8 cell, leaving a 1px gap on the right and bottom
+    const GLYPH_SCALE: u32 = 2;
+    const CELL_WIDTH: u32 = 6 * Self::GLYPH_SCALE;
+    const CELL_HEIGHT: u32 = 8 * Self::GLYPH_SCALE;
+
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    fn columns(&self) -> u32 {
+        self.width / Self::CELL_WIDTH
+    }
+    fn rows(&self) -> u32 {
+        self.height / Self::CELL_HEIGHT
+    }
+
+    fn clear(&mut self, color: HudColor) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    fn put_pixel(&mut self, x: u32, y: u32, color: HudColor) {
+        if x < self.width && y < self.height {
+            let i = ((y * self.width + x) * 4) as usize;
+            self.pixels[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    fn draw_glyph(&mut self, col: u32, row: u32, c: char, color: HudColor) {
+        let glyph = match c {
+            ' '..='~' => &FONT_5X7[(c as usize) - (' ' as usize)],
+            // Unsupported characters are drawn as '?'
+            _ => &FONT_5X7[('?' as usize) - (' ' as usize)],
+        };
+        let origin_x = col * Self::CELL_WIDTH;
+        let origin_y = row * Self::CELL_HEIGHT;
+        for (gx, bits) in glyph.iter().enumerate() {
+            for gy in 0..7 {
+                if bits & (1 << gy) == 0 {
+                    continue;
+                }
+                for sy in 0..Self::GLYPH_SCALE {
+                    for sx in 0..Self::GLYPH_SCALE {
+                        self.put_pixel(
+                            origin_x + (gx as u32) * Self::GLYPH_SCALE + sx,
+                            origin_y + gy * Self::GLYPH_SCALE + sy,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct Hud {
+    config: HudConfig,
+
+    view_space: xr::Space,
+    swapchain: Swapchain,
+
+    canvas: HudCanvas,
+    cursor_row: u32,
+    messages: VecDeque<String>,
+
+    pub stats: FrameStats,
+}
+impl Hud {
+    pub fn new(xr_shell: &XrShell, config: HudConfig) -> Result<Self> {
+        // The VIEW space is centered between the eyes and follows the head
+        let view_space = xr_shell
+            .xr_session
+            .create_reference_space(xr::ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?;
+        xr_shell.set_xr_object_name(xr::sys::ObjectType::SPACE, view_space.as_raw(), "hud view");
+
+        let (width, height) = config.resolution;
+        let swapchain = xr_shell.create_quad_swapchain("hud", width, height)?;
+
+        Ok(Self {
+            view_space,
+            swapchain,
+            canvas: HudCanvas::new(width, height),
+            cursor_row: 0,
+            messages: VecDeque::with_capacity(config.max_messages),
+            stats: FrameStats::default(),
+            config,
+        })
+    }
+
+    pub fn visible(&self) -> bool {
+        self.config.visible
+    }
+    pub fn set_visible(&mut self, visible: bool) {
+        self.config.visible = visible;
+    }
+    pub fn toggle(&mut self) {
+        self.config.visible = !self.config.visible;
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.config.distance
+    }
+    pub fn set_distance(&mut self, distance: f32) {
+        self.config.distance = distance;
+    }
+
+    /// Number of text columns and rows that fit on the HUD
+    pub fn text_size(&self) -> (u32, u32) {
+        (self.canvas.columns(), self.canvas.rows())
+    }
+
+    /// Clear the HUD and reset the [Hud::line] cursor to the top row
+    pub fn clear(&mut self) {
+        self.cursor_row = 0;
+        if !self.config.visible {
+            return;
+        }
+        self.canvas.clear(HUD_BACKGROUND);
+    }
+
+    /// Draw text starting at a given text cell. Text running off the right edge is clipped.
+    pub fn draw_text(&mut self, col: u32, row: u32, text: &str, color: HudColor) {
+        if !self.config.visible {
+            return;
+        }
+        let columns = self.canvas.columns();
+        for (i, c) in text.chars().enumerate() {
+            let col = col + i as u32;
+            if col >= columns {
+                break;
+            }
+            self.canvas.draw_glyph(col, row, c, color);
+        }
+    }
+
+    /// Draw a line of text at the cursor and move the cursor down one row
+    pub fn line(&mut self, text: &str) {
+        self.draw_text(0, self.cursor_row, text, HUD_TEXT);
+        self.cursor_row += 1;
+    }
+
+    /// Add a message to the scrolling message log, dropping the oldest one if full
+    pub fn push_message(&mut self, message: impl Into<String>) {
+        if self.messages.len() >= self.config.max_messages {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message.into());
+    }
+
+    pub fn draw_stats(&mut self) {
+        let stats = self.stats;
+        self.line(&format!(
+            "{:5.1} fps  cpu {:5.2}ms  display {:5.2}ms",
+            stats.fps, stats.cpu_frame_ms, stats.display_period_ms
+        ));
+    }
+
+    pub fn draw_session_state(&mut self, xr_shell: &XrShell) {
+        self.line(&format!("session {:?}", xr_shell.xr_session_state));
+    }
+
+    /// Draw the message log along the bottom rows, newest last
+    pub fn draw_messages(&mut self) {
+        let rows = self.canvas.rows();
+        let first_row = rows.saturating_sub(self.messages.len() as u32);
+        let messages = std::mem::take(&mut self.messages);
+        for (i, message) in messages.iter().enumerate() {
+            self.draw_text(0, first_row + i as u32, message, HUD_HIGHLIGHT);
+        }
+        self.messages = messages;
+    }
+
+    /// Frame stats, session state and the message log
+    pub fn draw_default(&mut self, xr_shell: &XrShell) {
+        self.draw_stats();
+        self.draw_session_state(xr_shell);
+        self.draw_messages();
+    }
+
+    pub fn swapchain(&self) -> &Swapchain {
+        &self.swapchain
+    }
+
+    /// Upload the canvas into the next swapchain image.
+    /// Must be called between xrBeginFrame and xrEndFrame, and only when the HUD is visible.
+    pub fn render(&mut self, xr_shell: &XrShell) -> Result<()> {
+        let mut swapchain = self.swapchain.handle.lock().unwrap();
+        let image_index = swapchain.acquire_image()?;
+        swapchain.wait_image(xr::Duration::INFINITE)?;
+
+        let resolution = self.swapchain.resolution;
+        xr_shell.wgpu_queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.swapchain.buffers[image_index as usize].texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.canvas.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(resolution.width * 4),
+                rows_per_image: Some(resolution.height),
+            },
+            wgpu::Extent3d {
+                width: resolution.width,
+                height: resolution.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        // Flush the pending write before handing the image back to the runtime
+        xr_shell.wgpu_queue.submit(std::iter::empty());
+
+        swapchain.release_image()?;
+        Ok(())
+    }
+
+    /// The quad layer to submit to xrEndFrame. `swapchain` must be the locked [Hud::swapchain] handle.
+    pub fn composition_layer<'a>(
+        &'a self,
+        swapchain: &'a xr::Swapchain<xr::Vulkan>,
+    ) -> xr::CompositionLayerQuad<'a, xr::Vulkan> {
+        let resolution = self.swapchain.resolution;
+        let aspect = resolution.height as f32 / resolution.width as f32;
+        xr::CompositionLayerQuad::new()
+            .layer_flags(xr::CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA)
+            .space(&self.view_space)
+            .eye_visibility(xr::EyeVisibility::BOTH)
+            .sub_image(
+                xr::SwapchainSubImage::new()
+                    .swapchain(swapchain)
+                    .image_array_index(0)
+                    .image_rect(xr::Rect2Di {
+                        offset: xr::Offset2Di { x: 0, y: 0 },
+                        extent: xr::Extent2Di {
+                            width: resolution.width as _,
+                            height: resolution.height as _,
+                        },
+                    }),
+            )
+            .pose(xr::Posef {
+                orientation: xr::Quaternionf {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                    w: 1.0,
+                },
+                position: xr::Vector3f {
+                    x: 0.0,
+                    y: self.config.vertical_offset,
+                    z: -self.config.distance,
+                },
+            })
+            .size(xr::Extent2Df {
+                width: self.config.width,
+                height: self.config.width * aspect,
+            })
+    }
+}
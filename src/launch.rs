@@ -0,0 +1,63 @@
+//! Options the app was launched with, from the command line on desktop or the intent on Android.
+
+use std::path::PathBuf;
+
+#[cfg(target_os = "android")]
+use anyhow::Result;
+
+#[derive(Debug, Clone, Default)]
+pub struct LaunchArgs {
+    /// Directory of images for the viewer to show
+    pub image_dir: Option<PathBuf>,
+}
+
+impl LaunchArgs {
+    /// Name of the intent string extra holding [LaunchArgs::image_dir], e.g.
+    /// `adb shell am start -n co.realfit.naopenxrwgpu/.MainActivity --es image_dir /sdcard/Pictures`
+    #[cfg(target_os = "android")]
+    pub const IMAGE_DIR_EXTRA: &'static str = "image_dir";
+
+    /// `test-openxr-wgpu [image_dir]`
+    #[cfg(not(target_os = "android"))]
+    pub fn from_command_line() -> Self {
+        Self {
+            image_dir: std::env::args_os().nth(1).map(PathBuf::from),
+        }
+    }
+
+    /// Read the extras of the intent which started the activity
+    #[cfg(target_os = "android")]
+    pub fn from_intent(android_app: &android_activity::AndroidApp) -> Result<Self> {
+        use jni::objects::{JObject, JString};
+
+        // Safety: both pointers are valid for the lifetime of the app
+        let vm = unsafe { jni::JavaVM::from_raw(android_app.vm_as_ptr() as _) }?;
+        let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as _) };
+        let mut env = vm.attach_current_thread()?;
+
+        let intent = env
+            .call_method(&activity, "getIntent", "()Landroid/content/Intent;", &[])?
+            .l()?;
+        if intent.is_null() {
+            return Ok(Self::default());
+        }
+
+        let key = env.new_string(Self::IMAGE_DIR_EXTRA)?;
+        let value = env
+            .call_method(
+                &intent,
+                "getStringExtra",
+                "(Ljava/lang/String;)Ljava/lang/String;",
+                &[(&key).into()],
+            )?
+            .l()?;
+        let image_dir = if value.is_null() {
+            None
+        } else {
+            let value: String = env.get_string(&JString::from(value))?.into();
+            Some(PathBuf::from(value))
+        };
+
+        Ok(Self { image_dir })
+    }
+}
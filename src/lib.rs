@@ -1,6 +1,6 @@
-use std::sync::atomic::Ordering;
+use std::{path::Path, sync::atomic::Ordering};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use ash::vk;
 
@@ -9,48 +9,150 @@ use wgpu_types as wgt;
 
 use openxr as xr;
 
+mod action_layers;
+mod bindings;
+mod buffers;
 mod game;
+mod gallery;
 mod controls;
+mod hud;
+#[cfg(not(target_os = "android"))]
+mod hot_reload;
+mod launch;
 mod math;
+mod mesh;
+mod model;
+mod pipeline_cache;
+mod shader;
+mod shader_bindings;
 mod shell;
-use shell::{PollStatus, XrShell};
+mod texture;
+mod vk_debug;
+mod xr_debug;
+use action_layers::ActionLayers;
+use hud::{Hud, HudConfig};
+use launch::LaunchArgs;
+use shell::{EyeViews, PollStatus, XrShell};
 
 #[cfg(target_os = "android")]
 use android_activity::AndroidApp;
 
 struct App<G: Game> {
     xr_shell: XrShell,
+    hud: Hud,
+    /// The game's action sets, synced every frame
+    action_layers: ActionLayers,
     game: G,
+    /// The last frame's views which had valid position and orientation, held while tracking is lost
+    last_valid_eye_views: Option<EyeViews>,
+    /// Watches the shader sources so they can be edited while running
+    #[cfg(not(target_os = "android"))]
+    shader_watcher: Option<hot_reload::ShaderWatcher>,
 }
 
 impl<G: Game> App<G> {
-    fn new() -> Result<Self> {
+    /// `pipeline_cache_dir` is where compiled pipelines are kept between runs
+    fn new(launch_args: &LaunchArgs, pipeline_cache_dir: Option<&Path>) -> Result<Self> {
         let vk_target_version = vk::make_api_version(0, 1, 1, 0); // Vulkan 1.1 guarantees multiview support
 
-        let features = wgpu::Features::SPIRV_SHADER_PASSTHROUGH | wgt::Features::MULTIVIEW;
+        #[allow(unused_mut)]
+        let mut features = wgt::Features::MULTIVIEW;
+        // Shaders are normally compiled by naga, only raw SPIR-V needs passthrough
+        #[cfg(feature = "spirv-passthrough")]
+        {
+            features |= wgt::Features::SPIRV_SHADER_PASSTHROUGH;
+        }
+        // Enabled when supported: compressed formats for loading KTX2 textures,
+        // and a pipeline cache to save compiling pipelines on every launch
+        let optional_features = wgt::Features::TEXTURE_COMPRESSION_ASTC
+            | wgt::Features::TEXTURE_COMPRESSION_ETC2
+            | wgt::Features::TEXTURE_COMPRESSION_BC
+            | wgt::Features::PIPELINE_CACHE;
         let limits = wgt::Limits::default();
 
-        let xr_shell = XrShell::new("OpenXR Wgpu", 1, vk_target_version, features, limits)?;
-        let game = G::init(&xr_shell)?;
+        let xr_shell = XrShell::new(
+            "OpenXR Wgpu",
+            1,
+            vk_target_version,
+            features,
+            optional_features,
+            limits,
+            pipeline_cache_dir,
+        )?;
+        let hud = Hud::new(&xr_shell, HudConfig::default())?;
+        let mut action_layers = ActionLayers::default();
+        let game = G::init(&xr_shell, launch_args, &mut action_layers)?;
+
+        #[cfg(not(target_os = "android"))]
+        let shader_watcher = hot_reload::ShaderWatcher::new()
+            .map_err(|e| log::warn!("Shader hot reload disabled: {e:#}"))
+            .ok();
 
         Ok(Self {
             xr_shell,
+            hud,
+            action_layers,
             game,
+            last_valid_eye_views: None,
+            #[cfg(not(target_os = "android"))]
+            shader_watcher,
         })
     }
 
     pub fn poll_events(&mut self) -> Result<PollStatus> {
-        self.xr_shell.poll_events()
+        let prev_session_state = self.xr_shell.xr_session_state;
+        let status = self.xr_shell.poll_events()?;
+        if self.xr_shell.xr_session_state != prev_session_state {
+            self.hud.push_message(format!("Session {:?}", self.xr_shell.xr_session_state));
+        }
+        if status.contains(PollStatus::REFERENCE_SPACE_CHANGED) {
+            for change in self.xr_shell.xr_reference_space_changes.iter() {
+                self.hud.push_message(format!("Reference space {:?} changed", change.space_type));
+                self.game.on_reference_space_changed(&self.xr_shell, change)?;
+            }
+        }
+        Ok(status)
+    }
+
+    /// Rebuild pipelines whose shaders changed on disk. Called between frames, so no frame is half recorded.
+    #[cfg(not(target_os = "android"))]
+    fn reload_changed_shaders(&mut self) {
+        let Some(shader_watcher) = &self.shader_watcher else {
+            return;
+        };
+        let changed = shader_watcher.poll_changed();
+        if changed.is_empty() {
+            return;
+        }
+        for path in changed.iter() {
+            log::info!("Shader changed: {}", path.display());
+            if let Some(name) = path.file_name() {
+                self.hud.push_message(format!("Reloading {}", name.to_string_lossy()));
+            }
+        }
+        if let Err(e) = self.game.on_shaders_changed(&self.xr_shell, &changed) {
+            log::error!("Reloading shaders: {e:#}");
+        }
     }
 
     pub fn frame_update(&mut self) -> Result<()> {
+        #[cfg(not(target_os = "android"))]
+        self.reload_changed_shaders();
+
         // Block until the previous frame is finished displaying, and is ready for another one.
         // Also returns a prediction of when the next frame will be displayed, for use with
         // predicting locations of controllers, viewpoints, etc.
         let frame_state = self.xr_shell.xr_frame_waiter.wait()?;
+        self.xr_shell.frame_index += 1;
 
+        // Input for this frame, from the layers the game set up in init
+        self.action_layers.sync(&self.xr_shell)?;
         self.game.tick_to(&self.xr_shell, frame_state.predicted_display_time);
 
+        self.hud.stats.record_frame(frame_state.predicted_display_period);
+        self.hud.clear();
+        self.game.update_hud(&self.xr_shell, &mut self.hud);
+
         // Spec: "An application must eventually match each xrWaitFrame call with one call to xrBeginFrame"
         self.xr_shell.xr_frame_stream.begin()?;
 
@@ -78,10 +180,39 @@ impl<G: Game> App<G> {
         render_status
     }
 
+    /// Finish a frame which has nothing to show
+    fn end_frame_without_layers(&mut self, frame_state: &xr::FrameState) -> Result<()> {
+        self.xr_shell.xr_frame_stream.end(
+            frame_state.predicted_display_time,
+            self.xr_shell.xr_current_blend_mode,
+            &[],
+        )?;
+        Ok(())
+    }
+
     pub fn render(&mut self, frame_state: xr::FrameState) -> Result<()> {
         log::info!("Render");
         debug_assert!(frame_state.should_render);
 
+        // Views are normally located late (see below), but until the head has been tracked once there
+        // may be nothing sensible to draw, so locate them before recording the scene rather than throw
+        // it away, and draw with those.
+        let early_eye_views = match self.last_valid_eye_views {
+            Some(_) => None,
+            None => {
+                let eye_views = self.xr_shell.locate_eye_views(
+                    frame_state.predicted_display_time,
+                    self.game.xr_stage(),
+                    None,
+                )?;
+                if eye_views.is_none() {
+                    log::warn!("View pose invalid, skipping frame");
+                    return self.end_frame_without_layers(&frame_state);
+                }
+                eye_views
+            }
+        };
+
         // We need to ask which swapchain image to use for rendering! Which one will we get?
         // Who knows! It's up to the runtime to decide.
         let image_index = self
@@ -111,13 +242,23 @@ impl<G: Game> App<G> {
         // rendering begins in earnest on the GPU. Uniforms dependent on this data can be sent
         // to the GPU just-in-time by writing them to per-frame host-visible memory which the
         // GPU will only read once the command buffer is submitted.
-        let (view_flags, views) = self.xr_shell.xr_session.locate_views(
-            XrShell::VIEW_TYPE,
-            frame_state.predicted_display_time,
-            self.game.xr_stage(),
-        )?;
+        let eye_views = match early_eye_views {
+            Some(eye_views) => eye_views,
+            // With valid views to hold there's always something to draw
+            None => self
+                .xr_shell
+                .locate_eye_views(
+                    frame_state.predicted_display_time,
+                    self.game.xr_stage(),
+                    self.last_valid_eye_views.as_ref(),
+                )?
+                .context("No eye views despite valid ones to hold")?,
+        };
+        if eye_views.is_fully_valid() {
+            self.last_valid_eye_views = Some(eye_views);
+        }
 
-        self.game.load_view_transforms(&self.xr_shell, view_flags, &views)?;
+        self.game.load_view_transforms(&self.xr_shell, &eye_views)?;
 
         self.xr_shell.wgpu_queue.submit(command_buffers);
 
@@ -128,6 +269,10 @@ impl<G: Game> App<G> {
             .unwrap()
             .release_image()?;
 
+        if self.hud.visible() {
+            self.hud.render(&self.xr_shell)?;
+        }
+
         // Tell OpenXR what to present for this frame
         let rect = xr::Rect2Di {
             offset: xr::Offset2Di { x: 0, y: 0 },
@@ -139,33 +284,50 @@ impl<G: Game> App<G> {
 
         let swapchain = &self.xr_shell.xr_swapchain.handle.lock().unwrap();
 
+        // Submit exactly the poses the eye matrices were built from, in the same space,
+        // so the runtime can reproject the image correctly.
+        let views = &eye_views.views;
+        let projection_views = [
+            xr::CompositionLayerProjectionView::new()
+                .pose(views[0].pose)
+                .fov(views[0].fov)
+                .sub_image(
+                    xr::SwapchainSubImage::new()
+                        .swapchain(swapchain)
+                        .image_array_index(0)
+                        .image_rect(rect),
+                ),
+            xr::CompositionLayerProjectionView::new()
+                .pose(views[1].pose)
+                .fov(views[1].fov)
+                .sub_image(
+                    xr::SwapchainSubImage::new()
+                        .swapchain(swapchain)
+                        .image_array_index(1)
+                        .image_rect(rect),
+                ),
+        ];
+        let projection_layer = xr::CompositionLayerProjection::new()
+            .space(self.game.xr_stage())
+            .views(&projection_views);
+
+        // Declared out here to outlive `layers`
+        let hud_swapchain;
+        let hud_layer;
+
+        // Layers are composited in order, so the HUD goes on top
+        let mut layers: Vec<&xr::CompositionLayerBase<xr::Vulkan>> = Vec::with_capacity(2);
+        layers.push(&projection_layer);
+        if self.hud.visible() {
+            hud_swapchain = self.hud.swapchain().handle.lock().unwrap();
+            hud_layer = self.hud.composition_layer(&hud_swapchain);
+            layers.push(&hud_layer);
+        }
+
         self.xr_shell.xr_frame_stream.end(
             frame_state.predicted_display_time,
             self.xr_shell.xr_current_blend_mode,
-            &[&xr::CompositionLayerProjection::new()
-                .space(self.game.xr_stage())
-                .views(&[
-                    // TODO use a custom Space here for world-space stuff instead of locking to camera view.
-                    // This information may be used for reprojection.
-                    xr::CompositionLayerProjectionView::new()
-                        .pose(views[0].pose)
-                        .fov(views[0].fov)
-                        .sub_image(
-                            xr::SwapchainSubImage::new()
-                                .swapchain(swapchain)
-                                .image_array_index(0)
-                                .image_rect(rect),
-                        ),
-                    xr::CompositionLayerProjectionView::new()
-                        .pose(views[1].pose)
-                        .fov(views[1].fov)
-                        .sub_image(
-                            xr::SwapchainSubImage::new()
-                                .swapchain(swapchain)
-                                .image_array_index(1)
-                                .image_rect(rect),
-                        ),
-                ])],
+            &layers,
         )?;
 
         Ok(())
@@ -178,7 +340,12 @@ impl<G: Game> App<G> {
 fn android_main(android_app: AndroidApp) {
     android_logger::init_once(android_logger::Config::default().with_min_level(log::Level::Trace));
 
-    let mut app = App::<game::RectViewer>::new().unwrap();
+    let launch_args = LaunchArgs::from_intent(&android_app).unwrap_or_else(|e| {
+        log::error!("Failed to read intent extras: {e:?}");
+        LaunchArgs::default()
+    });
+    let pipeline_cache_dir = android_app.internal_data_path();
+    let mut app = App::<game::RectViewer>::new(&launch_args, pipeline_cache_dir.as_deref()).unwrap();
 
     log::trace!("Running mainloop...");
     'mainloop: loop {
@@ -197,6 +364,10 @@ fn android_main(android_app: AndroidApp) {
             app.frame_update().unwrap();
         }
     }
+
+    if let Err(e) = app.xr_shell.save_pipeline_cache() {
+        log::error!("Failed to save pipeline cache: {e:?}");
+    }
 }
 
 #[allow(dead_code)]
@@ -207,7 +378,9 @@ fn main() -> Result<()> {
         .parse_default_env()
         .init();
 
-    let mut app = App::<game::RectViewer>::new().unwrap();
+    let launch_args = LaunchArgs::from_command_line();
+    let pipeline_cache_dir = dirs::cache_dir().map(|dir| dir.join("na-openxr-wgpu"));
+    let mut app = App::<game::RectViewer>::new(&launch_args, pipeline_cache_dir.as_deref()).unwrap();
 
     let r = app.xr_shell.quit_signal.clone();
     let _ = ctrlc::set_handler(move || {
@@ -228,5 +401,9 @@ fn main() -> Result<()> {
         }
     }
 
+    if let Err(e) = app.xr_shell.save_pipeline_cache() {
+        log::error!("Failed to save pipeline cache: {e:?}");
+    }
+
     Ok(())
 }
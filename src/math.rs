@@ -139,6 +139,15 @@ impl Pose {
     pub fn local_from_posed(self) -> Mat4 {
         self.posed_from_local().inverse().unwrap()
     }
+    /// Convert a glTF translation and (x, y, z, w) rotation by rotating it 180deg around Y
+    pub fn from_gltf(translation: [f32; 3], rotation: [f32; 4]) -> Pose {
+        let [tx, ty, tz] = translation;
+        let [qx, qy, qz, qw] = rotation;
+        Self {
+            position: Vec3([-tx, ty, -tz]),
+            orientation: Quat([-qx, qy, -qz, qw]),
+        }
+    }
 }
 impl From<xr::Posef> for Pose {
     fn from(value: xr::Posef) -> Self {
@@ -174,6 +183,23 @@ impl Mat4 {
             [0.0, 0.0, 0.0, 1.0],
         ])
     }
+    pub fn scale_xyz(v: impl Into<Vec3>) -> Self {
+        let v = v.into();
+        Self([
+            [v.0[0], 0.0, 0.0, 0.0],
+            [0.0, v.0[1], 0.0, 0.0],
+            [0.0, 0.0, v.0[2], 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    /// Right-handed rotation around +X
+    pub fn from_rotation_x(radians: f32) -> Mat4 {
+        cgmath::Matrix4::from_angle_x(cgmath::Rad(radians)).into()
+    }
+    /// Right-handed rotation around +Y
+    pub fn from_rotation_y(radians: f32) -> Mat4 {
+        cgmath::Matrix4::from_angle_y(cgmath::Rad(radians)).into()
+    }
     pub fn from_translation(v: impl Into<Vec3>) -> Mat4 {
         let v = v.into();
         cgmath::Matrix4::from_translation(v.into()).into()
@@ -209,6 +235,16 @@ impl Mat4 {
         ).into()
     }
 
+    /// 180deg rotation around Y, which converts glTF's coordinate space to OpenXR's and back
+    pub fn xr_from_gltf() -> Mat4 {
+        Self::scale_xyz([-1.0, 1.0, -1.0])
+    }
+
+    /// Convert a glTF node's `parent_from_node` matrix into OpenXR's coordinate space
+    pub fn from_gltf(parent_from_node: Mat4) -> Mat4 {
+        Self::xr_from_gltf() * parent_from_node * Self::xr_from_gltf()
+    }
+
     pub fn as_cg(self) -> cgmath::Matrix4<f32> {
         self.into()
     }
@@ -296,4 +332,60 @@ impl std::ops::Mul<Mat4> for Mat4 {
     fn mul(self, rhs: Mat4) -> Self::Output {
         (self.as_cg() * rhs.as_cg()).into()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Rotation3};
+
+    use super::*;
+
+    const TRANSLATION: [f32; 3] = [1.0, 2.0, 3.0];
+
+    /// glTF's (x, y, z, w) order
+    fn rotation() -> [f32; 4] {
+        let axis = cgmath::Vector3::new(1.0, 2.0, 3.0).normalize();
+        let q = cgmath::Quaternion::from_axis_angle(axis, cgmath::Rad(0.7));
+        [q.v.x, q.v.y, q.v.z, q.s]
+    }
+
+    /// The node's `parent_from_node` matrix, in glTF's space
+    fn gltf_matrix() -> Mat4 {
+        let [x, y, z, w] = rotation();
+        let rotation = cgmath::Matrix4::from(cgmath::Quaternion::new(w, x, y, z));
+        (cgmath::Matrix4::from_translation(TRANSLATION.into()) * rotation).into()
+    }
+
+    fn assert_mat4_eq(a: Mat4, b: Mat4) {
+        for (a, b) in a.0.iter().flatten().zip(b.0.iter().flatten()) {
+            assert!((a - b).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn mat4_from_gltf_round_trips() {
+        assert_mat4_eq(Mat4::from_gltf(Mat4::from_gltf(gltf_matrix())), gltf_matrix());
+    }
+
+    #[test]
+    fn pose_from_gltf_round_trips() {
+        let pose = Pose::from_gltf(TRANSLATION, rotation());
+        let back = Pose::from_gltf(pose.position.0, pose.orientation.0);
+        assert_eq!(back.position.0, TRANSLATION);
+        assert_eq!(back.orientation.0, rotation());
+    }
+
+    #[test]
+    fn pose_from_gltf_matches_mat4_from_gltf() {
+        let pose = Pose::from_gltf(TRANSLATION, rotation());
+        assert_mat4_eq(pose.posed_from_local(), Mat4::from_gltf(gltf_matrix()));
+    }
+
+    #[test]
+    fn converted_node_moves_converted_points() {
+        let point = cgmath::Vector4::new(0.5, -1.0, 2.0, 1.0);
+        let xr_from_gltf = Mat4::xr_from_gltf().as_cg();
+        let converted = Mat4::from_gltf(gltf_matrix()).as_cg() * (xr_from_gltf * point);
+        let expected = xr_from_gltf * (gltf_matrix().as_cg() * point);
+        assert!((converted - expected).magnitude() < 1e-5, "{converted:?} != {expected:?}");
+    }
+}
@@ -0,0 +1,503 @@
+//! Indexed meshes with typed vertex layouts, and procedural generators for common shapes.
+//!
+//! Geometry is built on the CPU as [MeshData], then uploaded into vertex and index buffers as a [Mesh].
+//! Generated shapes follow the [crate::math] conventions: +Y up, counter-clockwise front faces,
+//! and are centered on the origin unless stated otherwise.
+
+use std::f32::consts::{PI, TAU};
+
+use wgpu::util::DeviceExt;
+
+use crate::math::{Vec2, Vec3};
+use crate::shell::XrShell;
+
+/// A semantic vertex attribute. Each has a fixed shader location and format,
+/// so shaders can declare the attributes they use without knowing the rest of the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexAttribute {
+    /// `@location(0) vec3<f32>`
+    Position,
+    /// `@location(1) vec3<f32>`
+    Normal,
+    /// `@location(2) vec2<f32>`
+    Uv,
+    /// `@location(3) vec4<f32>`, linear RGBA
+    Color,
+}
+impl VertexAttribute {
+    pub const fn shader_location(self) -> u32 {
+        match self {
+            VertexAttribute::Position => 0,
+            VertexAttribute::Normal => 1,
+            VertexAttribute::Uv => 2,
+            VertexAttribute::Color => 3,
+        }
+    }
+
+    pub const fn format(self) -> wgpu::VertexFormat {
+        match self {
+            VertexAttribute::Position => wgpu::VertexFormat::Float32x3,
+            VertexAttribute::Normal => wgpu::VertexFormat::Float32x3,
+            VertexAttribute::Uv => wgpu::VertexFormat::Float32x2,
+            VertexAttribute::Color => wgpu::VertexFormat::Float32x4,
+        }
+    }
+}
+
+/// Description of a tightly packed, interleaved vertex buffer
+pub struct VertexLayout {
+    stride: u64,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+impl VertexLayout {
+    /// Lay out `attributes` one after the other in the order given
+    pub fn new(attributes: &[VertexAttribute]) -> Self {
+        let mut offset = 0;
+        let attributes = attributes
+            .iter()
+            .map(|attribute| {
+                let wgpu_attribute = wgpu::VertexAttribute {
+                    format: attribute.format(),
+                    offset,
+                    shader_location: attribute.shader_location(),
+                };
+                offset += attribute.format().size();
+                wgpu_attribute
+            })
+            .collect();
+        Self {
+            stride: offset,
+            attributes,
+        }
+    }
+
+    pub fn stride(&self) -> u64 {
+        self.stride
+    }
+
+    pub fn buffer_layout(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.stride,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+/// A `#[repr(C)]` vertex struct whose fields match a [VertexLayout]
+pub trait VertexType: bytemuck::Pod {
+    const ATTRIBUTES: &'static [VertexAttribute];
+
+    fn layout() -> VertexLayout {
+        let layout = VertexLayout::new(Self::ATTRIBUTES);
+        debug_assert_eq!(layout.stride(), std::mem::size_of::<Self>() as u64);
+        layout
+    }
+}
+
+/// The vertex format used by all the generated shapes
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    pub color: [f32; 4],
+}
+const _: () = assert!(std::mem::size_of::<Vertex>() == 48);
+impl VertexType for Vertex {
+    const ATTRIBUTES: &'static [VertexAttribute] = &[
+        VertexAttribute::Position,
+        VertexAttribute::Normal,
+        VertexAttribute::Uv,
+        VertexAttribute::Color,
+    ];
+}
+impl Vertex {
+    pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    pub fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Self {
+        Self {
+            position: position.into(),
+            normal: normal.into(),
+            uv: uv.into(),
+            color: Self::WHITE,
+        }
+    }
+}
+
+/// CPU-side geometry
+pub struct MeshData<V: VertexType> {
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+    pub topology: wgpu::PrimitiveTopology,
+}
+impl<V: VertexType> MeshData<V> {
+    pub fn upload(&self, xr_shell: &XrShell, label: Option<&str>) -> Mesh {
+        Mesh::new(xr_shell, self, label)
+    }
+}
+impl MeshData<Vertex> {
+    /// Set every vertex to the same color
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        for vertex in self.vertices.iter_mut() {
+            vertex.color = color;
+        }
+        self
+    }
+
+    /// Axis-aligned cube with side length `size`, with separate vertices per face for flat normals
+    pub fn cube(size: f32) -> Self {
+        let h = size / 2.0;
+        // (normal, u axis, v axis) for each face, where u x v = normal
+        let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+            ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+            ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+            ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ];
+
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for (n, u, v) in faces {
+            let base = vertices.len() as u32;
+            for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let position = [
+                    (n[0] + su * u[0] + sv * v[0]) * h,
+                    (n[1] + su * u[1] + sv * v[1]) * h,
+                    (n[2] + su * u[2] + sv * v[2]) * h,
+                ];
+                vertices.push(Vertex::new(position, n, [(su + 1.0) / 2.0, (1.0 - sv) / 2.0]));
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        Self {
+            vertices,
+            indices,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        }
+    }
+
+    /// UV sphere with `segments` divisions around +Y and `rings` divisions from pole to pole
+    pub fn sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let segments = segments.max(3);
+        let rings = rings.max(2);
+
+        let mut vertices = Vec::with_capacity(((segments + 1) * (rings + 1)) as usize);
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            // From the north pole (+Y) down to the south pole
+            let polar = v * PI;
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let azimuth = u * TAU;
+                let normal = [polar.sin() * azimuth.sin(), polar.cos(), polar.sin() * azimuth.cos()];
+                let position = [normal[0] * radius, normal[1] * radius, normal[2] * radius];
+                vertices.push(Vertex::new(position, normal, [u, v]));
+            }
+        }
+
+        let mut indices = Vec::with_capacity((segments * rings * 6) as usize);
+        let row = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * row + segment;
+                let b = a + row;
+                indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+
+        Self {
+            vertices,
+            indices,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        }
+    }
+
+    /// Flat rectangle in the XZ plane facing +Y
+    pub fn plane(width: f32, depth: f32) -> Self {
+        let (x, z) = (width / 2.0, depth / 2.0);
+        let up = [0.0, 1.0, 0.0];
+        Self {
+            vertices: vec![
+                Vertex::new([-x, 0.0, z], up, [0.0, 1.0]),
+                Vertex::new([x, 0.0, z], up, [1.0, 1.0]),
+                Vertex::new([x, 0.0, -z], up, [1.0, 0.0]),
+                Vertex::new([-x, 0.0, -z], up, [0.0, 0.0]),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        }
+    }
+
+    /// Capped cylinder along Y, from `-height/2` to `+height/2`
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+        let h = height / 2.0;
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        // Side, with a seam so the UVs wrap cleanly
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+            let normal = [sin, 0.0, cos];
+            vertices.push(Vertex::new([sin * radius, -h, cos * radius], normal, [u, 1.0]));
+            vertices.push(Vertex::new([sin * radius, h, cos * radius], normal, [u, 0.0]));
+        }
+        for segment in 0..segments {
+            let a = segment * 2;
+            indices.extend_from_slice(&[a, a + 2, a + 1, a + 1, a + 2, a + 3]);
+        }
+
+        // Caps, as triangle fans around a center vertex
+        for (y, ny) in [(h, 1.0), (-h, -1.0)] {
+            let center = vertices.len() as u32;
+            vertices.push(Vertex::new([0.0, y, 0.0], [0.0, ny, 0.0], [0.5, 0.5]));
+            for segment in 0..=segments {
+                let (sin, cos) = (segment as f32 / segments as f32 * TAU).sin_cos();
+                vertices.push(Vertex::new(
+                    [sin * radius, y, cos * radius],
+                    [0.0, ny, 0.0],
+                    [0.5 + sin * 0.5, 0.5 - cos * 0.5],
+                ));
+            }
+            for segment in 0..segments {
+                let a = center + 1 + segment;
+                if ny > 0.0 {
+                    indices.extend_from_slice(&[center, a, a + 1]);
+                } else {
+                    indices.extend_from_slice(&[center, a + 1, a]);
+                }
+            }
+        }
+
+        Self {
+            vertices,
+            indices,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        }
+    }
+
+    /// Square grid of lines in the XZ plane with `divisions` cells along each side.
+    /// Uses [wgpu::PrimitiveTopology::LineList], so needs a line pipeline.
+    pub fn grid(size: f32, divisions: u32) -> Self {
+        let divisions = divisions.max(1);
+        let h = size / 2.0;
+        let up = [0.0, 1.0, 0.0];
+
+        let mut vertices = Vec::with_capacity(((divisions + 1) * 4) as usize);
+        for i in 0..=divisions {
+            let t = i as f32 / divisions as f32;
+            let offset = -h + t * size;
+            // Line along X
+            vertices.push(Vertex::new([-h, 0.0, offset], up, [0.0, t]));
+            vertices.push(Vertex::new([h, 0.0, offset], up, [1.0, t]));
+            // Line along Z
+            vertices.push(Vertex::new([offset, 0.0, -h], up, [t, 0.0]));
+            vertices.push(Vertex::new([offset, 0.0, h], up, [t, 1.0]));
+        }
+        let indices = (0..vertices.len() as u32).collect();
+
+        Self {
+            vertices,
+            indices,
+            topology: wgpu::PrimitiveTopology::LineList,
+        }
+    }
+}
+
+/// GPU-side geometry
+pub struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    topology: wgpu::PrimitiveTopology,
+}
+impl Mesh {
+    pub fn new<V: VertexType>(xr_shell: &XrShell, data: &MeshData<V>, label: Option<&str>) -> Self {
+        let vertex_buffer = xr_shell
+            .wgpu_device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label,
+                contents: bytemuck::cast_slice(&data.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let index_buffer = xr_shell
+            .wgpu_device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label,
+                contents: bytemuck::cast_slice(&data.indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: data.indices.len() as u32,
+            topology: data.topology,
+        }
+    }
+
+    /// The pipeline this is drawn with must use this topology
+    pub fn topology(&self) -> wgpu::PrimitiveTopology {
+        self.topology
+    }
+
+    /// Bind the buffers and draw. The caller must have set the pipeline and bind groups.
+    pub fn enqueue_draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+    }
+
+    fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+    }
+
+    fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+
+    fn assert_counts(mesh: &MeshData<Vertex>, vertices: usize, indices: usize) {
+        assert_eq!(mesh.vertices.len(), vertices);
+        assert_eq!(mesh.indices.len(), indices);
+    }
+
+    fn assert_indices_in_range(mesh: &MeshData<Vertex>) {
+        let count = mesh.vertices.len() as u32;
+        assert!(mesh.indices.iter().all(|&i| i < count));
+    }
+
+    /// Every non-degenerate triangle winds counter-clockwise when seen from the side its normals face
+    fn assert_front_faces_along_normals(mesh: &MeshData<Vertex>) {
+        assert_eq!(mesh.topology, wgpu::PrimitiveTopology::TriangleList);
+        assert_eq!(mesh.indices.len() % 3, 0);
+        for (t, triangle) in mesh.indices.chunks_exact(3).enumerate() {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+            let face = cross(sub(b.position.0, a.position.0), sub(c.position.0, a.position.0));
+            if dot(face, face) < 1e-10 {
+                // e.g. where a sphere's rows meet at a pole
+                continue;
+            }
+            let normal = [a, b, c]
+                .iter()
+                .fold([0.0; 3], |sum, vertex| [0, 1, 2].map(|i| sum[i] + vertex.normal.0[i]));
+            assert!(dot(face, normal) > 0.0, "triangle {t} {triangle:?} winds against its normals");
+        }
+    }
+
+    fn assert_unit_normals(mesh: &MeshData<Vertex>) {
+        for vertex in mesh.vertices.iter() {
+            assert!((dot(vertex.normal.0, vertex.normal.0) - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn vertex_layout_packs_attributes_in_order() {
+        let layout = Vertex::layout();
+        assert_eq!(layout.stride(), 48);
+        let placed: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|attribute| (attribute.offset, attribute.shader_location))
+            .collect();
+        assert_eq!(placed, [(0, 0), (12, 1), (24, 2), (32, 3)]);
+    }
+
+    #[test]
+    fn vertex_layout_keeps_shader_locations_when_attributes_are_skipped() {
+        let layout = VertexLayout::new(&[VertexAttribute::Uv, VertexAttribute::Position]);
+        assert_eq!(layout.stride(), 20);
+        let placed: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|attribute| (attribute.offset, attribute.shader_location, attribute.format))
+            .collect();
+        assert_eq!(
+            placed,
+            [(0, 2, wgpu::VertexFormat::Float32x2), (8, 0, wgpu::VertexFormat::Float32x3)]
+        );
+    }
+
+    #[test]
+    fn cube() {
+        let cube = MeshData::cube(2.0);
+        assert_counts(&cube, 24, 36);
+        assert_indices_in_range(&cube);
+        assert_front_faces_along_normals(&cube);
+        assert_unit_normals(&cube);
+        for vertex in cube.vertices.iter() {
+            assert!(vertex.position.0.iter().all(|x| x.abs() == 1.0));
+        }
+    }
+
+    #[test]
+    fn sphere() {
+        let sphere = MeshData::sphere(2.0, 8, 4);
+        assert_counts(&sphere, 9 * 5, 8 * 4 * 6);
+        assert_indices_in_range(&sphere);
+        assert_front_faces_along_normals(&sphere);
+        assert_unit_normals(&sphere);
+        for vertex in sphere.vertices.iter() {
+            assert!((dot(vertex.position.0, vertex.position.0).sqrt() - 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn sphere_has_a_minimum_resolution() {
+        let sphere = MeshData::sphere(1.0, 0, 0);
+        assert_counts(&sphere, 4 * 3, 3 * 2 * 6);
+        assert_indices_in_range(&sphere);
+    }
+
+    #[test]
+    fn plane() {
+        let plane = MeshData::plane(2.0, 4.0);
+        assert_counts(&plane, 4, 6);
+        assert_indices_in_range(&plane);
+        assert_front_faces_along_normals(&plane);
+        for vertex in plane.vertices.iter() {
+            let [x, y, z] = vertex.position.0;
+            assert_eq!((x.abs(), y, z.abs()), (1.0, 0.0, 2.0));
+        }
+    }
+
+    #[test]
+    fn cylinder() {
+        let cylinder = MeshData::cylinder(1.0, 2.0, 8);
+        // A side with a seam, and two caps of a center and a seamed ring
+        assert_counts(&cylinder, 2 * 9 + 2 * (1 + 9), 8 * 6 + 2 * 8 * 3);
+        assert_indices_in_range(&cylinder);
+        assert_front_faces_along_normals(&cylinder);
+        assert_unit_normals(&cylinder);
+    }
+
+    #[test]
+    fn grid() {
+        let grid = MeshData::grid(2.0, 4);
+        assert_eq!(grid.topology, wgpu::PrimitiveTopology::LineList);
+        // Two lines per division boundary
+        assert_counts(&grid, 5 * 4, 5 * 4);
+        assert_indices_in_range(&grid);
+        for line in grid.vertices.chunks_exact(2) {
+            let [a, b] = [line[0].position.0, line[1].position.0];
+            // Each line spans the grid along one axis, at a fixed offset along the other
+            let along = sub(b, a);
+            assert_eq!(dot(along, along), 4.0);
+            assert!(a[1] == 0.0 && b[1] == 0.0);
+        }
+    }
+}
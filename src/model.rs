@@ -0,0 +1,780 @@
+//! glTF 2.0 scene loading.
+//!
+//! Everything in a [Model] has already been converted from glTF's coordinate space into OpenXR's
+//! (see [crate::math]): vertex positions and normals are rotated 180deg around Y, and so are node
+//! transforms (via [Mat4::from_gltf] and [Pose::from_gltf]), so a [Model] can be placed directly
+//! in the stage space with a `world_from_model` matrix.
+//!
+//! Meshes are uploaded with the [Vertex] layout, with the material's base color factor baked into
+//! the vertex colors. Textures are decoded to RGBA8 on the CPU, and those used as a base color are
+//! uploaded through [TextureLoader] and bound per material, see [Model::base_color_bind_group].
+//! The rest of each [Material] (metallic-roughness, normal, occlusion and emissive) is imported but
+//! not yet shaded by `mesh.wgsl`.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::{
+    math::{Mat4, Pose, Vec3},
+    mesh::{Mesh, MeshData, Vertex},
+    shell::XrShell,
+    texture::{Texture, TextureLoader},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fully transparent below the cutoff, fully opaque otherwise
+    Mask { cutoff: f32 },
+    Blend,
+}
+
+/// A reference from a [Material] to one of [Model::textures]
+#[derive(Debug, Clone, Copy)]
+pub struct TextureRef {
+    pub texture: usize,
+    /// Which UV set to sample with. Only set 0 is imported.
+    pub tex_coord: u32,
+}
+impl From<gltf::texture::Info<'_>> for TextureRef {
+    fn from(info: gltf::texture::Info<'_>) -> Self {
+        Self {
+            texture: info.texture().index(),
+            tex_coord: info.tex_coord(),
+        }
+    }
+}
+
+/// PBR metallic-roughness material
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: Option<String>,
+    /// Linear RGBA
+    pub base_color_factor: [f32; 4],
+    /// sRGB
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Linear, roughness in G and metalness in B
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    /// Linear RGB
+    pub emissive_factor: [f32; 3],
+    /// sRGB
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+impl Material {
+    fn from_gltf(material: &gltf::Material) -> Self {
+        let pbr = material.pbr_metallic_roughness();
+        let alpha_mode = match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask {
+                cutoff: material.alpha_cutoff().unwrap_or(0.5),
+            },
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        };
+        Self {
+            name: material.name().map(String::from),
+            base_color_factor: pbr.base_color_factor(),
+            base_color_texture: pbr.base_color_texture().map(Into::into),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            metallic_roughness_texture: pbr.metallic_roughness_texture().map(Into::into),
+            normal_texture: material.normal_texture().map(|t| TextureRef {
+                texture: t.texture().index(),
+                tex_coord: t.tex_coord(),
+            }),
+            normal_scale: material.normal_texture().map_or(1.0, |t| t.scale()),
+            occlusion_texture: material.occlusion_texture().map(|t| TextureRef {
+                texture: t.texture().index(),
+                tex_coord: t.tex_coord(),
+            }),
+            occlusion_strength: material.occlusion_texture().map_or(1.0, |t| t.strength()),
+            emissive_factor: material.emissive_factor(),
+            emissive_texture: material.emissive_texture().map(Into::into),
+            alpha_mode,
+            double_sided: material.double_sided(),
+        }
+    }
+}
+impl Default for Material {
+    /// The glTF default material
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0; 3],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+/// A decoded texture image and how to sample it
+pub struct TextureImage {
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed RGBA8 texels. Whether they are sRGB depends on which [Material] slot uses them.
+    pub rgba8: Vec<u8>,
+    pub sampler: wgpu::SamplerDescriptor<'static>,
+}
+impl TextureImage {
+    fn from_gltf(texture: &gltf::Texture, images: &[gltf::image::Data]) -> Result<Self> {
+        let image = &images[texture.source().index()];
+        let rgba8 = image_to_rgba8(image)
+            .with_context(|| format!("Decoding image {} of texture {}", texture.source().index(), texture.index()))?;
+        Ok(Self {
+            name: texture.name().map(String::from),
+            width: image.width,
+            height: image.height,
+            rgba8,
+            sampler: sampler_from_gltf(&texture.sampler()),
+        })
+    }
+}
+
+fn image_to_rgba8(image: &gltf::image::Data) -> Result<Vec<u8>> {
+    use gltf::image::Format;
+
+    // (channels, bytes per channel)
+    let (channels, channel_size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let texel_size = channels * channel_size;
+    let texel_count = (image.width * image.height) as usize;
+    if image.pixels.len() != texel_count * texel_size {
+        anyhow::bail!(
+            "Expected {} bytes for {}x{} {:?}, got {}",
+            texel_count * texel_size,
+            image.width,
+            image.height,
+            image.format,
+            image.pixels.len()
+        );
+    }
+
+    let channel_to_u8 = |bytes: &[u8]| -> u8 {
+        match channel_size {
+            1 => bytes[0],
+            // Little-endian, keep the most significant byte
+            2 => bytes[1],
+            _ => {
+                let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        }
+    };
+
+    let mut rgba8 = Vec::with_capacity(texel_count * 4);
+    for texel in image.pixels.chunks_exact(texel_size) {
+        let mut out = [0, 0, 0, 255];
+        for (c, channel) in texel.chunks_exact(channel_size).enumerate() {
+            out[c] = channel_to_u8(channel);
+        }
+        // Greyscale is replicated into RGB, as glTF only uses single-channel images for data
+        // which is read from R anyway
+        if channels == 1 {
+            out[1] = out[0];
+            out[2] = out[0];
+        }
+        rgba8.extend_from_slice(&out);
+    }
+    Ok(rgba8)
+}
+
+fn sampler_from_gltf(sampler: &gltf::texture::Sampler) -> wgpu::SamplerDescriptor<'static> {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode: WrappingMode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => wgpu::FilterMode::Linear,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
+        Some(MinFilter::Linear) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapNearest) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
+        Some(MinFilter::LinearMipmapNearest) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapLinear) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear),
+        Some(MinFilter::LinearMipmapLinear) | None => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
+    };
+    wgpu::SamplerDescriptor {
+        label: None,
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        ..Default::default()
+    }
+}
+
+/// One draw call's worth of a glTF mesh
+pub struct Primitive {
+    pub mesh: Mesh,
+    /// Index into [Model::materials], or `None` for the glTF default material
+    pub material: Option<usize>,
+}
+
+pub struct ModelMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+pub struct Node {
+    pub name: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Index into [Model::meshes]
+    pub mesh: Option<usize>,
+    pub parent_from_node: Mat4,
+    /// Accumulated from the root of the scene
+    pub model_from_node: Mat4,
+}
+
+/// A loaded glTF scene
+pub struct Model {
+    /// Every node in the file, indexed as in the file
+    pub nodes: Vec<Node>,
+    /// The nodes at the root of the scene which was loaded
+    pub roots: Vec<usize>,
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<TextureImage>,
+    /// Each of [Model::textures] which is used as a base color, uploaded as sRGB
+    pub gpu_textures: Vec<Option<Texture>>,
+    /// Each of [Model::materials]' base color texture bound with its sampler, if it has one.
+    /// Laid out as [TextureLoader::material_layout].
+    pub base_color_bind_groups: Vec<Option<wgpu::BindGroup>>,
+}
+
+impl Model {
+    /// Load a `.gltf` (with external or embedded buffers) or `.glb` file
+    pub fn load(xr_shell: &XrShell, textures: &mut TextureLoader, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let (document, buffers, images) =
+            gltf::import(path).with_context(|| format!("Importing glTF {}", path.display()))?;
+        Self::from_gltf(xr_shell, textures, &document, &buffers, &images)
+            .with_context(|| format!("Loading glTF {}", path.display()))
+    }
+
+    /// Load a self-contained `.glb`, or a `.gltf` with only embedded (data URI) buffers
+    pub fn from_slice(xr_shell: &XrShell, textures: &mut TextureLoader, bytes: &[u8]) -> Result<Self> {
+        let (document, buffers, images) = gltf::import_slice(bytes).context("Importing glTF")?;
+        Self::from_gltf(xr_shell, textures, &document, &buffers, &images)
+    }
+
+    fn from_gltf(
+        xr_shell: &XrShell,
+        texture_loader: &mut TextureLoader,
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data],
+    ) -> Result<Self> {
+        let materials: Vec<Material> = document.materials().map(|m| Material::from_gltf(&m)).collect();
+
+        let textures = document
+            .textures()
+            .map(|t| TextureImage::from_gltf(&t, images))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Only the base color is shaded, so other slots' textures stay on the CPU
+        let mut gpu_textures: Vec<Option<Texture>> = textures.iter().map(|_| None).collect();
+        for texture_ref in materials.iter().filter_map(|m| m.base_color_texture) {
+            if texture_ref.tex_coord != 0 {
+                log::warn!("Base color texture {} uses UV set {}, sampling set 0 instead", texture_ref.texture, texture_ref.tex_coord);
+            }
+            let image = &textures[texture_ref.texture];
+            gpu_textures[texture_ref.texture].get_or_insert_with(|| {
+                texture_loader.from_rgba8(xr_shell, image.width, image.height, &image.rgba8, true, image.name.as_deref())
+            });
+        }
+        let base_color_bind_groups = materials
+            .iter()
+            .map(|material| {
+                let texture_ref = material.base_color_texture?;
+                let texture = gpu_textures[texture_ref.texture].as_ref()?;
+                Some(texture_loader.bind(xr_shell, texture, &textures[texture_ref.texture].sampler))
+            })
+            .collect();
+
+        let meshes = document
+            .meshes()
+            .map(|mesh| {
+                let primitives = mesh
+                    .primitives()
+                    .enumerate()
+                    .filter_map(|(i, primitive)| {
+                        let label = format!("{}[{}]", mesh.name().unwrap_or("mesh"), i);
+                        let material = primitive.material().index();
+                        let base_color = material.map_or([1.0; 4], |m| materials[m].base_color_factor);
+                        match load_primitive(&primitive, buffers, base_color) {
+                            Ok(Some(data)) => Some(Ok(Primitive {
+                                mesh: data.upload(xr_shell, Some(&label)),
+                                material,
+                            })),
+                            Ok(None) => {
+                                log::warn!("Skipping glTF primitive {label} without positions");
+                                None
+                            }
+                            Err(e) => Some(Err(e.context(format!("Loading glTF primitive {label}")))),
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(ModelMesh {
+                    name: mesh.name().map(String::from),
+                    primitives,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut nodes: Vec<Node> = document
+            .nodes()
+            .map(|node| {
+                let parent_from_node = match node.transform() {
+                    gltf::scene::Transform::Matrix { matrix } => Mat4::from_gltf(Mat4(matrix)),
+                    gltf::scene::Transform::Decomposed {
+                        translation,
+                        rotation,
+                        scale,
+                    } => {
+                        // A 180deg rotation around Y leaves an axis-aligned scale unchanged
+                        Pose::from_gltf(translation, rotation).posed_from_local() * Mat4::scale_xyz(scale)
+                    }
+                };
+                Node {
+                    name: node.name().map(String::from),
+                    parent: None,
+                    children: node.children().map(|c| c.index()).collect(),
+                    mesh: node.mesh().map(|m| m.index()),
+                    parent_from_node,
+                    model_from_node: parent_from_node,
+                }
+            })
+            .collect();
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .context("glTF has no scenes")?;
+        let roots: Vec<usize> = scene.nodes().map(|n| n.index()).collect();
+
+        // glTF guarantees the node hierarchy is a forest, so a depth-first walk visits each node once
+        let mut stack: Vec<(usize, Option<usize>)> = roots.iter().map(|&root| (root, None)).collect();
+        while let Some((index, parent)) = stack.pop() {
+            let model_from_parent = parent.map_or(Mat4::identity(), |p| nodes[p].model_from_node);
+            let node = &mut nodes[index];
+            node.parent = parent;
+            node.model_from_node = model_from_parent * node.parent_from_node;
+            stack.extend(node.children.iter().map(|&child| (child, Some(index))));
+        }
+
+        log::info!(
+            "Loaded glTF: {} nodes, {} meshes, {} materials, {} textures",
+            nodes.len(),
+            meshes.len(),
+            materials.len(),
+            textures.len()
+        );
+
+        Ok(Self {
+            nodes,
+            roots,
+            meshes,
+            materials,
+            textures,
+            gpu_textures,
+            base_color_bind_groups,
+        })
+    }
+
+    /// The base color texture to draw `primitive` with, or `None` if its material is untextured
+    pub fn base_color_bind_group(&self, primitive: &Primitive) -> Option<&wgpu::BindGroup> {
+        self.base_color_bind_groups[primitive.material?].as_ref()
+    }
+
+    /// Every primitive in the loaded scene, with its `world_from_model` transform
+    pub fn draws(&self, world_from_model: Mat4) -> impl Iterator<Item = (Mat4, &Primitive)> + '_ {
+        let mut stack: Vec<usize> = self.roots.clone();
+        let mut scene_nodes = Vec::new();
+        while let Some(index) = stack.pop() {
+            scene_nodes.push(index);
+            stack.extend(self.nodes[index].children.iter().copied());
+        }
+        scene_nodes.into_iter().flat_map(move |index| {
+            let node = &self.nodes[index];
+            let world_from_node = world_from_model * node.model_from_node;
+            node.mesh
+                .into_iter()
+                .flat_map(move |mesh| self.meshes[mesh].primitives.iter())
+                .map(move |primitive| (world_from_node, primitive))
+        })
+    }
+}
+
+/// Read a primitive into [Vertex] data, converting it into OpenXR's coordinate space and
+/// any strip, fan, or loop topology into a list.
+/// Returns `None` if the primitive has no positions.
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+    base_color: [f32; 4],
+) -> Result<Option<MeshData<Vertex>>> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let Some(positions) = reader.read_positions() else {
+        return Ok(None);
+    };
+    let mut vertices: Vec<Vertex> = positions
+        .map(|p| Vertex::new(gltf_to_xr(p), [0.0, 0.0, 0.0], [0.0, 0.0]))
+        .collect();
+
+    if let Some(normals) = reader.read_normals() {
+        for (vertex, n) in vertices.iter_mut().zip(normals) {
+            vertex.normal = gltf_to_xr(n).into();
+        }
+    }
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+            vertex.uv = uv.into();
+        }
+    }
+    let colors = reader.read_colors(0).map(|c| c.into_rgba_f32());
+    match colors {
+        Some(colors) => {
+            for (vertex, c) in vertices.iter_mut().zip(colors) {
+                vertex.color = std::array::from_fn(|i| c[i] * base_color[i]);
+            }
+        }
+        None => {
+            for vertex in vertices.iter_mut() {
+                vertex.color = base_color;
+            }
+        }
+    }
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    if let Some(&bad) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+        anyhow::bail!("Index {bad} out of range for {} vertices", vertices.len());
+    }
+
+    let (topology, indices) = list_topology(primitive.mode(), indices);
+
+    let mut data = MeshData {
+        vertices,
+        indices,
+        topology,
+    };
+    if reader.read_normals().is_none() && topology == wgpu::PrimitiveTopology::TriangleList {
+        compute_flat_normals(&mut data);
+    }
+    Ok(Some(data))
+}
+
+/// Convert strip, fan and loop indices into the equivalent list, keeping the winding of each triangle
+fn list_topology(mode: gltf::mesh::Mode, indices: Vec<u32>) -> (wgpu::PrimitiveTopology, Vec<u32>) {
+    use gltf::mesh::Mode;
+    match mode {
+        Mode::Points => (wgpu::PrimitiveTopology::PointList, indices),
+        Mode::Lines => (wgpu::PrimitiveTopology::LineList, indices),
+        Mode::LineStrip => (
+            wgpu::PrimitiveTopology::LineList,
+            indices.windows(2).flatten().copied().collect(),
+        ),
+        Mode::LineLoop => {
+            let closed = indices.iter().chain(indices.first()).copied().collect::<Vec<_>>();
+            (
+                wgpu::PrimitiveTopology::LineList,
+                closed.windows(2).flatten().copied().collect(),
+            )
+        }
+        Mode::Triangles => (wgpu::PrimitiveTopology::TriangleList, indices),
+        Mode::TriangleStrip => (
+            wgpu::PrimitiveTopology::TriangleList,
+            indices
+                .windows(3)
+                .enumerate()
+                // Every other triangle is flipped to keep the winding consistent
+                .flat_map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] })
+                .collect(),
+        ),
+        Mode::TriangleFan => (
+            wgpu::PrimitiveTopology::TriangleList,
+            indices
+                .windows(2)
+                .skip(1)
+                .flat_map(|t| [indices[0], t[0], t[1]])
+                .collect(),
+        ),
+    }
+}
+
+/// glTF says to use flat normals when none are given. Vertices shared between faces
+/// are duplicated so each face gets its own normal.
+fn compute_flat_normals(data: &mut MeshData<Vertex>) {
+    let mut vertices = Vec::with_capacity(data.indices.len());
+    for triangle in data.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| cgmath::Vector3::from(data.vertices[triangle[i] as usize].position));
+        let normal = (b - a).cross(c - a);
+        let normal = if normal == cgmath::Vector3::new(0.0, 0.0, 0.0) {
+            normal
+        } else {
+            cgmath::InnerSpace::normalize(normal)
+        };
+        for &index in triangle {
+            let mut vertex = data.vertices[index as usize];
+            vertex.normal = Vec3::from(normal);
+            vertices.push(vertex);
+        }
+    }
+    data.indices = (0..vertices.len() as u32).collect();
+    data.vertices = vertices;
+}
+
+/// Rotate a glTF position or direction 180deg around Y into OpenXR's space
+fn gltf_to_xr(v: [f32; 3]) -> [f32; 3] {
+    [-v[0], v[1], -v[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use gltf::image::Format;
+    use gltf::mesh::Mode;
+
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    /// A binary glTF with one primitive drawn with `mode` (as numbered in the glTF spec),
+    /// from `positions` and, if given, `indices`
+    fn glb(mode: u32, positions: &[[f32; 3]], indices: Option<&[u32]>) -> Vec<u8> {
+        let mut bin: Vec<u8> = bytemuck::cast_slice(positions).to_vec();
+        let positions_length = bin.len();
+        bin.extend_from_slice(bytemuck::cast_slice(indices.unwrap_or(&[])));
+
+        let min: [f32; 3] = std::array::from_fn(|i| positions.iter().map(|p| p[i]).fold(f32::MAX, f32::min));
+        let max: [f32; 3] = std::array::from_fn(|i| positions.iter().map(|p| p[i]).fold(f32::MIN, f32::max));
+        let (index_accessor, index_view) = match indices {
+            Some(indices) => (
+                format!(r#",{{"bufferView":1,"componentType":5125,"count":{},"type":"SCALAR"}}"#, indices.len()),
+                format!(
+                    r#",{{"buffer":0,"byteOffset":{positions_length},"byteLength":{}}}"#,
+                    bin.len() - positions_length
+                ),
+            ),
+            None => (String::new(), String::new()),
+        };
+        let indices_attribute = if indices.is_some() { r#","indices":1"# } else { "" };
+        let json = format!(
+            r#"{{"asset":{{"version":"2.0"}},
+            "buffers":[{{"byteLength":{}}}],
+            "bufferViews":[{{"buffer":0,"byteLength":{positions_length}}}{index_view}],
+            "accessors":[{{"bufferView":0,"componentType":5126,"count":{},"type":"VEC3","min":{min:?},"max":{max:?}}}{index_accessor}],
+            "meshes":[{{"primitives":[{{"attributes":{{"POSITION":0}}{indices_attribute},"mode":{mode}}}]}}]}}"#,
+            bin.len(),
+            positions.len(),
+        );
+
+        let pad = |mut chunk: Vec<u8>, with: u8| {
+            chunk.resize(chunk.len().next_multiple_of(4), with);
+            chunk
+        };
+        let (json, bin) = (pad(json.into_bytes(), b' '), pad(bin, 0));
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        for (chunk, kind) in [(json, b"JSON"), (bin, b"BIN\0")] {
+            glb.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            glb.extend_from_slice(kind);
+            glb.extend_from_slice(&chunk);
+        }
+        glb
+    }
+
+    fn load(glb: &[u8]) -> Result<Option<MeshData<Vertex>>> {
+        let (document, buffers, _) = gltf::import_slice(glb)?;
+        let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+        load_primitive(&primitive, &buffers, RED)
+    }
+
+    /// A triangle facing +Z in glTF's space
+    const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    #[test]
+    fn lists_are_unchanged() {
+        for (mode, topology) in [
+            (Mode::Points, wgpu::PrimitiveTopology::PointList),
+            (Mode::Lines, wgpu::PrimitiveTopology::LineList),
+            (Mode::Triangles, wgpu::PrimitiveTopology::TriangleList),
+        ] {
+            assert_eq!(list_topology(mode, vec![0, 1, 2, 3, 4, 5]), (topology, vec![0, 1, 2, 3, 4, 5]));
+        }
+    }
+
+    #[test]
+    fn line_strip_to_list() {
+        let (topology, indices) = list_topology(Mode::LineStrip, vec![0, 1, 2, 3]);
+        assert_eq!(topology, wgpu::PrimitiveTopology::LineList);
+        assert_eq!(indices, [0, 1, 1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn line_loop_to_list() {
+        let (topology, indices) = list_topology(Mode::LineLoop, vec![0, 1, 2]);
+        assert_eq!(topology, wgpu::PrimitiveTopology::LineList);
+        assert_eq!(indices, [0, 1, 1, 2, 2, 0]);
+        assert!(list_topology(Mode::LineLoop, vec![]).1.is_empty());
+    }
+
+    #[test]
+    fn triangle_strip_to_list_keeps_winding() {
+        let (topology, indices) = list_topology(Mode::TriangleStrip, vec![0, 1, 2, 3, 4]);
+        assert_eq!(topology, wgpu::PrimitiveTopology::TriangleList);
+        // The spec's (1, 3, 2) is the same triangle with the same winding as (2, 1, 3)
+        assert_eq!(indices, [0, 1, 2, 2, 1, 3, 2, 3, 4]);
+        assert!(list_topology(Mode::TriangleStrip, vec![0, 1]).1.is_empty());
+    }
+
+    #[test]
+    fn triangle_fan_to_list() {
+        let (topology, indices) = list_topology(Mode::TriangleFan, vec![0, 1, 2, 3]);
+        assert_eq!(topology, wgpu::PrimitiveTopology::TriangleList);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+        assert!(list_topology(Mode::TriangleFan, vec![0, 1]).1.is_empty());
+    }
+
+    #[test]
+    fn gltf_to_xr_turns_around_y() {
+        assert_eq!(gltf_to_xr([1.0, 2.0, 3.0]), [-1.0, 2.0, -3.0]);
+        assert_eq!(gltf_to_xr(gltf_to_xr([1.0, 2.0, 3.0])), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn flat_normals_split_shared_vertices() {
+        // Two triangles of a unit square facing +Y, sharing an edge
+        let mut data = MeshData {
+            vertices: [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 0.0, 0.0]]
+                .map(|p| Vertex::new(p, [0.0; 3], [0.0; 2]))
+                .to_vec(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        };
+        compute_flat_normals(&mut data);
+        assert_eq!(data.vertices.len(), 6);
+        assert_eq!(data.indices, [0, 1, 2, 3, 4, 5]);
+        for vertex in data.vertices.iter() {
+            assert_eq!(vertex.normal.0, [0.0, 1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn degenerate_triangle_gets_no_normal() {
+        let mut data = MeshData {
+            vertices: vec![Vertex::new([1.0, 2.0, 3.0], [0.0; 3], [0.0; 2]); 3],
+            indices: vec![0, 1, 2],
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        };
+        compute_flat_normals(&mut data);
+        assert!(data.vertices.iter().all(|vertex| vertex.normal.0 == [0.0; 3]));
+    }
+
+    #[test]
+    fn primitive_is_turned_into_xr_space() {
+        let data = load(&glb(4, &TRIANGLE, None)).unwrap().unwrap();
+        assert_eq!(data.topology, wgpu::PrimitiveTopology::TriangleList);
+        let positions: Vec<_> = data.vertices.iter().map(|vertex| vertex.position.0).collect();
+        assert_eq!(positions, TRIANGLE.map(gltf_to_xr));
+        for vertex in data.vertices.iter() {
+            // Facing +Z in glTF is facing -Z in OpenXR
+            assert_eq!(vertex.normal.0, [0.0, 0.0, -1.0]);
+            assert_eq!(vertex.color, RED);
+        }
+    }
+
+    #[test]
+    fn indexed_strip_is_loaded_as_a_list() {
+        let square = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]];
+        let data = load(&glb(5, &square, Some(&[0, 1, 2, 3]))).unwrap().unwrap();
+        // Flat normals split the vertices, and both triangles face the same way
+        assert_eq!(data.vertices.len(), 6);
+        assert!(data.vertices.iter().all(|vertex| vertex.normal.0 == [0.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn index_out_of_range_is_an_error() {
+        let Err(error) = load(&glb(4, &TRIANGLE, Some(&[0, 1, 3]))) else {
+            panic!("Index 3 was accepted");
+        };
+        assert_eq!(error.to_string(), "Index 3 out of range for 3 vertices");
+    }
+
+    fn image(format: Format, width: u32, height: u32, pixels: Vec<u8>) -> gltf::image::Data {
+        gltf::image::Data { pixels, format, width, height }
+    }
+
+    #[test]
+    fn grey_is_replicated_and_opaque() {
+        let rgba8 = image_to_rgba8(&image(Format::R8, 2, 1, vec![10, 200])).unwrap();
+        assert_eq!(rgba8, [10, 10, 10, 255, 200, 200, 200, 255]);
+    }
+
+    #[test]
+    fn missing_channels_are_filled_in() {
+        let rgba8 = image_to_rgba8(&image(Format::R8G8, 1, 1, vec![10, 20])).unwrap();
+        assert_eq!(rgba8, [10, 20, 0, 255]);
+        let rgba8 = image_to_rgba8(&image(Format::R8G8B8, 1, 1, vec![10, 20, 30])).unwrap();
+        assert_eq!(rgba8, [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn sixteen_bit_keeps_the_high_byte() {
+        let pixels = [0x1234u16, 0xabcd, 0xffff, 0x00ff].iter().flat_map(|c| c.to_le_bytes()).collect();
+        let rgba8 = image_to_rgba8(&image(Format::R16G16B16A16, 1, 1, pixels)).unwrap();
+        assert_eq!(rgba8, [0x12, 0xab, 0xff, 0x00]);
+    }
+
+    #[test]
+    fn float_is_clamped_and_rounded() {
+        let pixels = [-1.0f32, 0.5, 2.0].iter().flat_map(|c| c.to_le_bytes()).collect();
+        let rgba8 = image_to_rgba8(&image(Format::R32G32B32FLOAT, 1, 1, pixels)).unwrap();
+        assert_eq!(rgba8, [0, 128, 255, 255]);
+    }
+
+    #[test]
+    fn wrong_size_is_an_error() {
+        assert!(image_to_rgba8(&image(Format::R8G8B8A8, 2, 2, vec![0; 15])).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! A [wgpu::PipelineCache] persisted between runs, so pipelines don't need compiling from
+//! scratch on every launch.
+//!
+//! The cache file is named with [wgpu::util::pipeline_cache_key], which changes with the adapter and
+//! driver, so an update never loads incompatible data. wgpu also checks the data it's given and
+//! starts empty if it doesn't match (`fallback: true`).
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+pub struct PersistentPipelineCache {
+    cache: wgpu::PipelineCache,
+    path: PathBuf,
+}
+
+impl PersistentPipelineCache {
+    /// Create the cache, with the data saved in `dir` by a previous run if there is any.
+    /// `None` if the device doesn't have [wgpu::Features::PIPELINE_CACHE], or the adapter has no cache key.
+    pub fn load(adapter: &wgpu::Adapter, device: &wgpu::Device, dir: &Path) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            log::info!("Pipeline cache not supported");
+            return None;
+        }
+        let Some(key) = wgpu::util::pipeline_cache_key(&adapter.get_info()) else {
+            log::info!("No pipeline cache key for this adapter");
+            return None;
+        };
+        let path = dir.join(key);
+
+        let data = match std::fs::read(&path) {
+            Ok(data) => {
+                log::info!("Loaded {} byte pipeline cache from {}", data.len(), path.display());
+                Some(data)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::warn!("Failed to read pipeline cache {}: {e}", path.display());
+                None
+            }
+        };
+
+        // Safety: the data was written by [PersistentPipelineCache::save] for an adapter with the same
+        // cache key, and wgpu validates its header, falling back to an empty cache if it doesn't match
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("pipeline_cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Some(Self { cache, path })
+    }
+
+    pub fn cache(&self) -> &wgpu::PipelineCache {
+        &self.cache
+    }
+
+    /// Write the cache's current contents back to disk.
+    /// Written to a temporary file then renamed, so a crash mid-write can't leave a truncated cache.
+    pub fn save(&self) -> Result<()> {
+        let Some(data) = self.cache.get_data() else {
+            return Ok(());
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Creating {}", dir.display()))?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, &data).with_context(|| format!("Writing {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Renaming {} to {}", temp_path.display(), self.path.display()))?;
+        log::info!("Saved {} byte pipeline cache to {}", data.len(), self.path.display());
+        Ok(())
+    }
+}
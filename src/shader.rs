@@ -0,0 +1,250 @@
+//! Compiling shaders from source at runtime.
+//!
+//! WGSL, GLSL and SPIR-V are parsed and validated with naga before being handed to wgpu as naga IR,
+//! so mistakes come back as [anyhow] errors pointing at the file, line and column instead of
+//! panicking inside wgpu. The language is chosen by file extension, see [ShaderLanguage::from_path].
+//!
+//! Multiview shaders should be written in WGSL, using `@builtin(view_index)`. naga's GLSL frontend
+//! doesn't support `GL_EXT_multiview`, so `gl_ViewIndex` is rejected with an error saying so.
+//! Existing multiview GLSL can still be used by compiling it to SPIR-V offline (e.g. with `glslc`)
+//! and loading the `.spv`.
+//!
+//! With the `spirv-passthrough` feature, SPIR-V is given to the driver untouched (and unvalidated)
+//! instead of going through naga.
+
+use std::{borrow::Cow, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::shell::XrShell;
+
+/// Create a shader module from a file in `src/`, embedded at compile time.
+/// The path must be a string literal, relative to `src/`.
+macro_rules! include_shader {
+    ($xr_shell:expr, $path:literal) => {
+        $crate::shader::create_shader_module($xr_shell, $path, include_bytes!($path))
+    };
+}
+pub(crate) use include_shader;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Wgsl,
+    Glsl(naga::ShaderStage),
+    SpirV,
+}
+
+impl ShaderLanguage {
+    /// `.wgsl`, `.vert`/`.frag`/`.comp` for GLSL, or `.spv`
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .with_context(|| format!("Shader {} has no file extension", path.display()))?;
+        Ok(match extension {
+            "wgsl" => Self::Wgsl,
+            "vert" => Self::Glsl(naga::ShaderStage::Vertex),
+            "frag" => Self::Glsl(naga::ShaderStage::Fragment),
+            "comp" => Self::Glsl(naga::ShaderStage::Compute),
+            "spv" => Self::SpirV,
+            other => anyhow::bail!("Unknown shader extension .{other} for {}", path.display()),
+        })
+    }
+}
+
+/// A `path:line:column: message` error, followed by naga's rendering of the source snippet if there is one
+fn located_error(
+    name: &str,
+    location: Option<naga::SourceLocation>,
+    message: impl std::fmt::Display,
+    snippet: Option<String>,
+) -> anyhow::Error {
+    let mut text = match location {
+        Some(location) => format!(
+            "{name}:{}:{}: {message}",
+            location.line_number, location.line_position
+        ),
+        None => format!("{name}: {message}"),
+    };
+    if let Some(snippet) = snippet {
+        text.push('\n');
+        text.push_str(&snippet);
+    }
+    anyhow!(text)
+}
+
+pub fn parse_wgsl(name: &str, source: &str) -> Result<naga::Module> {
+    naga::front::wgsl::parse_str(source).map_err(|e| {
+        located_error(
+            name,
+            e.location(source),
+            e.message(),
+            Some(e.emit_to_string_with_path(source, name)),
+        )
+    })
+}
+
+pub fn parse_glsl(name: &str, source: &str, stage: naga::ShaderStage) -> Result<naga::Module> {
+    // Give a clear error rather than naga's "unknown variable"
+    if let Some(offset) = source.find("gl_ViewIndex") {
+        let span = naga::Span::new(offset as u32, (offset + "gl_ViewIndex".len()) as u32);
+        return Err(located_error(
+            name,
+            Some(span.location(source)),
+            "gl_ViewIndex (GL_EXT_multiview) isn't supported by naga's GLSL frontend. \
+             Port the shader to WGSL and use @builtin(view_index), or compile it to SPIR-V",
+            None,
+        ));
+    }
+
+    naga::front::glsl::Frontend::default()
+        .parse(&naga::front::glsl::Options::from(stage), source)
+        .map_err(|errors| {
+            // Report the first error by location, but keep all of them in the message
+            let first = errors.errors.first();
+            located_error(
+                name,
+                first.and_then(|e| e.location(source)),
+                first.map_or_else(|| "GLSL parse error".to_string(), |e| e.kind.to_string()),
+                Some(errors.emit_to_string(source)),
+            )
+        })
+}
+
+pub fn parse_spirv(name: &str, bytes: &[u8]) -> Result<naga::Module> {
+    naga::front::spv::parse_u8_slice(bytes, &Default::default())
+        .map_err(|e| located_error(name, None, e, None))
+}
+
+/// Validate a module against what the device supports. `source` is only used for error locations.
+pub fn validate(
+    xr_shell: &XrShell,
+    name: &str,
+    source: Option<&str>,
+    module: &naga::Module,
+) -> Result<naga::valid::ModuleInfo> {
+    let features = xr_shell.wgpu_device.features();
+    let mut capabilities = naga::valid::Capabilities::empty();
+    capabilities.set(
+        naga::valid::Capabilities::MULTIVIEW,
+        features.contains(wgpu::Features::MULTIVIEW),
+    );
+    capabilities.set(
+        naga::valid::Capabilities::PUSH_CONSTANT,
+        features.contains(wgpu::Features::PUSH_CONSTANTS),
+    );
+
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities)
+        .validate(module)
+        .map_err(|e| {
+            let (location, snippet) = match source {
+                Some(source) => (e.location(source), Some(e.emit_to_string_with_path(source, name))),
+                None => (None, None),
+            };
+            located_error(name, location, e.as_inner(), snippet)
+        })
+}
+
+/// Parse and validate shader source (or SPIR-V words) in the language given by `name`'s extension
+pub fn compile(xr_shell: &XrShell, name: &str, bytes: &[u8]) -> Result<naga::Module> {
+    let language = ShaderLanguage::from_path(name)?;
+    let (module, source) = match language {
+        ShaderLanguage::Wgsl | ShaderLanguage::Glsl(_) => {
+            let source = std::str::from_utf8(bytes).with_context(|| format!("{name} is not UTF-8"))?;
+            let module = match language {
+                ShaderLanguage::Glsl(stage) => parse_glsl(name, source, stage)?,
+                _ => parse_wgsl(name, source)?,
+            };
+            (module, Some(source))
+        }
+        ShaderLanguage::SpirV => (parse_spirv(name, bytes)?, None),
+    };
+    validate(xr_shell, name, source, &module)?;
+    Ok(module)
+}
+
+/// Create a shader module from source (or SPIR-V words) in the language given by `name`'s extension
+pub fn create_shader_module(xr_shell: &XrShell, name: &str, bytes: &[u8]) -> Result<wgpu::ShaderModule> {
+    #[cfg(feature = "spirv-passthrough")]
+    if ShaderLanguage::from_path(name)? == ShaderLanguage::SpirV {
+        // Safety: SPIR-V passthrough skips all validation, the shader must be valid for the device
+        return Ok(unsafe {
+            xr_shell
+                .wgpu_device
+                .create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV {
+                    label: Some(name),
+                    source: wgpu::util::make_spirv_raw(bytes),
+                })
+        });
+    }
+
+    let module = compile(xr_shell, name, bytes)?;
+    Ok(xr_shell
+        .wgpu_device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(name),
+            source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+        }))
+}
+
+/// Read and compile a shader file at runtime
+pub fn load_shader_module(xr_shell: &XrShell, path: impl AsRef<Path>) -> Result<wgpu::ShaderModule> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).with_context(|| format!("Reading shader {}", path.display()))?;
+    create_shader_module(xr_shell, &path.to_string_lossy(), &bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_from_extension() {
+        assert_eq!(ShaderLanguage::from_path("a.wgsl").unwrap(), ShaderLanguage::Wgsl);
+        assert_eq!(
+            ShaderLanguage::from_path("a.frag").unwrap(),
+            ShaderLanguage::Glsl(naga::ShaderStage::Fragment)
+        );
+        assert_eq!(ShaderLanguage::from_path("a.spv").unwrap(), ShaderLanguage::SpirV);
+        assert!(ShaderLanguage::from_path("a.hlsl").is_err());
+        assert!(ShaderLanguage::from_path("a").is_err());
+    }
+
+    #[test]
+    fn located_error_formats_position_and_snippet() {
+        let location = naga::SourceLocation {
+            line_number: 3,
+            line_position: 7,
+            offset: 0,
+            length: 1,
+        };
+        let error = located_error("a.wgsl", Some(location), "bad", Some("snippet".to_string()));
+        assert_eq!(error.to_string(), "a.wgsl:3:7: bad\nsnippet");
+        assert_eq!(located_error("a.spv", None, "bad", None).to_string(), "a.spv: bad");
+    }
+
+    #[test]
+    fn wgsl_error_is_located() {
+        let source = "@vertex\nfn vs_main() -> @builtin(position) vec4<f32> {\n    return vec4<f32>(oops);\n}\n";
+        let message = parse_wgsl("bad.wgsl", source).unwrap_err().to_string();
+        assert!(message.starts_with("bad.wgsl:3:22: "), "{message}");
+        assert!(message.contains("oops"), "{message}");
+    }
+
+    #[test]
+    fn glsl_view_index_is_rejected_where_it_is_used() {
+        let source = "#version 450\nlayout(location=0) out vec4 color;\nvoid main() { color = vec4(gl_ViewIndex); }\n";
+        let message = parse_glsl("multiview.frag", source, naga::ShaderStage::Fragment)
+            .unwrap_err()
+            .to_string();
+        assert!(message.starts_with("multiview.frag:3:28: "), "{message}");
+        assert!(message.contains("gl_ViewIndex"), "{message}");
+    }
+
+    #[test]
+    fn glsl_without_view_index_parses() {
+        let source = "#version 450\nlayout(location=0) out vec4 color;\nvoid main() { color = vec4(1.0); }\n";
+        parse_glsl("plain.frag", source, naga::ShaderStage::Fragment).unwrap();
+    }
+}
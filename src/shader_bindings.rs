@@ -0,0 +1,12 @@
+//! Bind group layouts reflected from the WGSL shaders at build time.
+//!
+//! `build.rs` parses every shader in `src/shaders/wgsl` with naga and generates a module for each
+//! bind group layout shared between shaders, listed in its `SHARED_GROUPS`. Each module has the
+//! `LAYOUT_ENTRIES` to create the layout with, and a module per binding with its index and, for
+//! buffers, the size and member offsets to check the Rust `#[repr(C)]` structs against.
+//! The build fails if shaders sharing a layout disagree about it.
+
+// Not every generated constant has a use
+#![allow(dead_code)]
+
+include!(concat!(env!("OUT_DIR"), "/shader_bindings.rs"));
@@ -0,0 +1,6 @@
+// Port of glsl/debug_pattern.frag: shows the quad coordinates in red/green, and which eye in blue.
+
+@fragment
+fn fs_main(@location(0) xy: vec2<f32>, @builtin(view_index) view_index: i32) -> @location(0) vec4<f32> {
+    return vec4<f32>(xy.x, xy.y, f32(view_index), 1.0);
+}
@@ -0,0 +1,30 @@
+// Port of glsl/fullscreen.vert: a unit quad in the XY plane, transformed into each eye.
+
+struct Eyes {
+    eye_screen_from_world: array<mat4x4<f32>, 2>,
+}
+
+struct PerObject {
+    world_from_model: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> eyes: Eyes;
+@group(0) @binding(1) var<uniform> object: PerObject;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) xy: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, @builtin(view_index) view_index: i32) -> VertexOutput {
+    var vertices = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let vertex = vertices[vertex_index];
+    var out: VertexOutput;
+    out.clip_position = eyes.eye_screen_from_world[view_index] * object.world_from_model * vec4<f32>(vertex * 0.5, 0.0, 1.0);
+    out.xy = (vertex + 1.0) / 2.0;
+    return out;
+}
@@ -0,0 +1,52 @@
+// Lit meshes using the layout from mesh.rs, coloured by their vertex colours times a texture.
+// Group 0 matches SceneBindings in game.rs, group 1 matches TextureLoader::material_layout_entries in texture.rs.
+// Untextured meshes are drawn with a 1x1 white texture.
+
+struct Eyes {
+    eye_screen_from_world: array<mat4x4<f32>, 2>,
+}
+
+struct PerObject {
+    world_from_model: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> eyes: Eyes;
+@group(0) @binding(1) var<uniform> object: PerObject;
+
+@group(1) @binding(0) var material_texture: texture_2d<f32>;
+@group(1) @binding(1) var material_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput, @builtin(view_index) view_index: i32) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = object.world_from_model * vec4<f32>(in.position, 1.0);
+    out.clip_position = eyes.eye_screen_from_world[view_index] * world_position;
+    // Assumes world_from_model has no non-uniform scale
+    out.world_normal = (object.world_from_model * vec4<f32>(in.normal, 0.0)).xyz;
+    out.uv = in.uv;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let light_dir = normalize(vec3<f32>(0.3, 1.0, 0.5));
+    let normal = normalize(in.world_normal);
+    let lambert = 0.35 + 0.65 * max(dot(normal, light_dir), 0.0);
+    let color = in.color * textureSample(material_texture, material_sampler, in.uv);
+    return vec4<f32>(color.rgb * lambert, color.a);
+}
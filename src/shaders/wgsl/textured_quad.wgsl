@@ -0,0 +1,40 @@
+// A unit quad showing a texture, the same shape as fullscreen.vert.
+// Group 0 matches SceneBindings in game.rs, group 1 matches TextureLoader::material_layout_entries in texture.rs.
+
+struct Eyes {
+    eye_screen_from_world: array<mat4x4<f32>, 2>,
+}
+
+struct PerObject {
+    world_from_model: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> eyes: Eyes;
+@group(0) @binding(1) var<uniform> object: PerObject;
+
+@group(1) @binding(0) var material_texture: texture_2d<f32>;
+@group(1) @binding(1) var material_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, @builtin(view_index) view_index: i32) -> VertexOutput {
+    var vertices = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let xy = vertices[vertex_index];
+    var out: VertexOutput;
+    out.clip_position = eyes.eye_screen_from_world[view_index] * object.world_from_model * vec4<f32>(xy * 0.5, 0.0, 1.0);
+    // Images are stored top row first, but +Y is up
+    out.uv = vec2<f32>(xy.x + 1.0, 1.0 - xy.y) * 0.5;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(material_texture, material_sampler, in.uv);
+}
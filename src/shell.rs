@@ -1,5 +1,5 @@
 use std::{
-    collections::HashSet, ffi::{CStr, CString}, hash::Hash, sync::{
+    collections::HashSet, ffi::{CStr, CString}, hash::Hash, path::Path, sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
     }, time::Duration
@@ -14,9 +14,14 @@ use ash::vk::{self, Handle};
 use wgpu_hal as hal;
 use wgpu_types as wgt;
 
+use crate::math::{Mat4, Pose};
+use crate::pipeline_cache::PersistentPipelineCache;
+use crate::vk_debug::{VkDebugConfig, VkDebugMessenger, VkObjectNamer};
+use crate::xr_debug::{self, XrDebugMessenger};
 use crate::xr;
 
 pub struct Framebuffer {
+    pub texture: wgpu::Texture,
     pub color: wgpu::TextureView,
 }
 
@@ -26,6 +31,28 @@ pub struct Swapchain {
     pub resolution: vk::Extent2D,
 }
 
+/// The views one frame is rendered with.
+///
+/// The same poses and fovs must be used both to build the eye matrices and in the frame's
+/// [xr::CompositionLayerProjectionView]s. Otherwise the runtime reprojects the image
+/// from a pose it wasn't rendered at, which shows up as judder.
+#[derive(Debug, Clone, Copy)]
+pub struct EyeViews {
+    pub views: [xr::View; 2],
+    /// Flags returned by xrLocateViews for this frame
+    pub flags: xr::ViewStateFlags,
+    /// Whether part of the poses was carried over from an earlier frame because tracking was invalid
+    pub held: bool,
+}
+impl EyeViews {
+    pub fn is_fully_valid(&self) -> bool {
+        !self.held
+            && self
+                .flags
+                .contains(xr::ViewStateFlags::ORIENTATION_VALID | xr::ViewStateFlags::POSITION_VALID)
+    }
+}
+
 // xr::EnvironmentBlendMode doesn't currently implement Hash
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub struct XrBlendMode(pub xr::EnvironmentBlendMode);
@@ -40,6 +67,140 @@ bitflags! {
     pub struct PollStatus: u32 {
         const QUIT  = 1<<0;
         const FRAME = 1<<1;
+        /// At least one reference space will change origin, see [XrShell::xr_reference_space_changes]
+        const REFERENCE_SPACE_CHANGED = 1<<2;
+    }
+}
+
+/// A runtime announcement that a reference space's origin will move, e.g. because the user recentered
+#[derive(Debug, Clone, Copy)]
+pub struct ReferenceSpaceChange {
+    pub space_type: xr::ReferenceSpaceType,
+    /// Frames displayed from this time on use the new origin
+    pub change_time: xr::Time,
+}
+
+/// The reference spaces a game can ask for with [XrShell::create_tracking_space], in order of preference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackingSpaceKind {
+    /// Origin on the floor at the center of the user's configured play area
+    Stage,
+    /// XR_EXT_local_floor: origin on the floor below the starting head position
+    LocalFloor,
+    /// Origin at the starting head position, moved down by an estimated standing eye height in meters
+    LocalWithEstimatedFloor { eye_height: f32 },
+}
+
+/// A reference space chosen by [XrShell::create_tracking_space].
+/// Content placed relative to it has +Y up and the origin (approximately) on the floor.
+pub struct TrackingSpace {
+    pub space: xr::Space,
+    pub kind: TrackingSpaceKind,
+    /// Pose of the space in its underlying reference space, before any [TrackingSpace::recenter]
+    base_pose: xr::Posef,
+    /// When to undo any recenter, see [TrackingSpace::reset_at]
+    pending_reset: Option<xr::Time>,
+}
+impl TrackingSpace {
+    pub fn reference_space_type(&self) -> xr::ReferenceSpaceType {
+        match self.kind {
+            TrackingSpaceKind::Stage => xr::ReferenceSpaceType::STAGE,
+            TrackingSpaceKind::LocalFloor => XrShell::local_floor_reference_space_type(),
+            TrackingSpaceKind::LocalWithEstimatedFloor { .. } => xr::ReferenceSpaceType::LOCAL,
+        }
+    }
+
+    /// Move the origin to the floor below the user's head, facing the same way as the head (yaw only).
+    pub fn recenter(&mut self, xr_shell: &XrShell, time: xr::Time) -> Result<()> {
+        let base_space = xr_shell
+            .xr_session
+            .create_reference_space(self.reference_space_type(), xr::Posef::IDENTITY)?;
+        let view_space = xr_shell
+            .xr_session
+            .create_reference_space(xr::ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?;
+        let head = view_space.locate(&base_space, time)?;
+
+        let valid = xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID;
+        if !head.location_flags.contains(valid) {
+            return Err(anyhow!("Can't recenter, head pose is not valid"));
+        }
+
+        // Keep only the rotation around +Y
+        let q = head.pose.orientation;
+        let yaw = (2.0 * (q.w * q.y + q.x * q.z)).atan2(1.0 - 2.0 * (q.x * q.x + q.y * q.y));
+        let pose = xr::Posef {
+            orientation: xr::Quaternionf {
+                x: 0.0,
+                y: (yaw / 2.0).sin(),
+                z: 0.0,
+                w: (yaw / 2.0).cos(),
+            },
+            position: xr::Vector3f {
+                x: head.pose.position.x,
+                y: self.base_pose.position.y,
+                z: head.pose.position.z,
+            },
+        };
+
+        self.space = xr_shell
+            .xr_session
+            .create_reference_space(self.reference_space_type(), pose)?;
+        self.name_space(xr_shell);
+        log::info!("Recentered {:?} tracking space", self.kind);
+        Ok(())
+    }
+
+    fn name_space(&self, xr_shell: &XrShell) {
+        xr_shell.set_xr_object_name(
+            xr::sys::ObjectType::SPACE,
+            self.space.as_raw(),
+            &format!("{:?} tracking space", self.kind),
+        );
+    }
+
+    /// Undo any [TrackingSpace::recenter], e.g. because the runtime has recentered the underlying space itself.
+    pub fn reset(&mut self, xr_shell: &XrShell) -> Result<()> {
+        self.space = xr_shell
+            .xr_session
+            .create_reference_space(self.reference_space_type(), self.base_pose)?;
+        self.name_space(xr_shell);
+        self.pending_reset = None;
+        Ok(())
+    }
+
+    /// [TrackingSpace::reset] once frames reach `change_time`, when the runtime's own recenter of the
+    /// underlying space takes effect. Until then the old origin is still current.
+    pub fn reset_at(&mut self, change_time: xr::Time) {
+        self.pending_reset = Some(change_time);
+    }
+
+    /// Apply a reset scheduled with [TrackingSpace::reset_at] if `time` has reached it. Call once per frame.
+    pub fn update(&mut self, xr_shell: &XrShell, time: xr::Time) -> Result<()> {
+        match self.pending_reset {
+            Some(change_time) if time.as_nanos() >= change_time.as_nanos() => self.reset(xr_shell),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The user's play area: an axis-aligned rectangle on the floor, centered on the STAGE origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StageBounds {
+    /// Extent along X in meters
+    pub width: f32,
+    /// Extent along Z in meters
+    pub depth: f32,
+}
+impl StageBounds {
+    /// Corners on the floor in STAGE space, counter-clockwise when seen from above
+    pub fn corners(&self) -> [[f32; 3]; 4] {
+        let (x, z) = (self.width / 2.0, self.depth / 2.0);
+        [[-x, 0.0, -z], [-x, 0.0, z], [x, 0.0, z], [x, 0.0, -z]]
+    }
+
+    /// Whether a STAGE-space point lies inside the bounds, ignoring height
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        point[0].abs() <= self.width / 2.0 && point[2].abs() <= self.depth / 2.0
     }
 }
 
@@ -52,6 +213,14 @@ pub struct XrShell {
     pub wgpu_adapter: wgpu::Adapter,
     pub wgpu_device: wgpu::Device,
     pub wgpu_queue: wgpu::Queue,
+    /// See [XrShell::pipeline_cache]
+    wgpu_pipeline_cache: Option<PersistentPipelineCache>,
+    /// Routes validation messages to the log while it's alive, see [crate::vk_debug]
+    _vk_debug_messenger: Option<VkDebugMessenger>,
+    /// Names the Vulkan objects the shell creates, when `VK_EXT_debug_utils` is enabled
+    vk_object_namer: Option<VkObjectNamer>,
+    /// Routes OpenXR runtime messages to the log while it's alive, see [crate::xr_debug]
+    _xr_debug_messenger: Option<XrDebugMessenger>,
 
     pub xr_frame_waiter: xr::FrameWaiter,
     pub xr_frame_stream: xr::FrameStream<xr::vulkan::Vulkan>,
@@ -64,12 +233,42 @@ pub struct XrShell {
 
     pub quit_signal: Arc<AtomicBool>,
     pub session_running: bool,
+    pub xr_session_state: xr::SessionState,
+
+    /// Whether XR_EXT_local_floor was available and enabled
+    pub xr_local_floor_enabled: bool,
+    /// Reference space origin changes announced during the last [XrShell::poll_events]
+    pub xr_reference_space_changes: Vec<ReferenceSpaceChange>,
+    /// See [XrShell::stage_bounds]
+    xr_stage_bounds: Option<StageBounds>,
+    /// For [XrShell::locate_stage_bounds], created once there are bounds to locate
+    xr_stage_space: Option<xr::Space>,
+
+    /// Incremented by the app at the start of every frame
+    pub frame_index: u64,
 }
 
 impl XrShell {
     pub const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
     pub const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
 
+    /// Number of frames the CPU may run ahead of the GPU, see [crate::buffers]
+    pub const FRAMES_IN_FLIGHT: usize = 3;
+
+    pub const LOCAL_FLOOR_EXTENSION_NAME: &'static str = "XR_EXT_local_floor";
+    /// Standing eye height used to guess where the floor is when only LOCAL is available
+    pub const DEFAULT_EYE_HEIGHT: f32 = 1.6;
+    pub const DEFAULT_TRACKING_SPACE_PREFERENCES: &'static [TrackingSpaceKind] = &[
+        TrackingSpaceKind::Stage,
+        TrackingSpaceKind::LocalFloor,
+        TrackingSpaceKind::LocalWithEstimatedFloor { eye_height: Self::DEFAULT_EYE_HEIGHT },
+    ];
+
+    /// XR_REFERENCE_SPACE_TYPE_LOCAL_FLOOR_EXT, which our openxr bindings predate
+    pub fn local_floor_reference_space_type() -> xr::ReferenceSpaceType {
+        xr::ReferenceSpaceType::from_raw(1000426000)
+    }
+
     fn hal_instance_flags() -> wgpu::InstanceFlags {
         let mut flags = wgpu::InstanceFlags::empty();
         if cfg!(debug_assertions) {
@@ -100,7 +299,7 @@ impl XrShell {
         app_version: u32,
         vk_target_version: u32,
         hal_instance_flags: wgpu::InstanceFlags,
-    ) -> Result<(ash::Instance, <hal::api::Vulkan as hal::Api>::Instance)> {
+    ) -> Result<(ash::Instance, <hal::api::Vulkan as hal::Api>::Instance, Option<VkDebugMessenger>)> {
         let entry = unsafe { ash::Entry::load()? };
 
         let instance_extensions = unsafe { entry.enumerate_instance_extension_properties(None)? };
@@ -206,6 +405,10 @@ impl XrShell {
             layers
         };
 
+        // Only when wgpu-hal wanted the extension, which keeps the Quest workaround in hal_instance_flags()
+        let debug_utils_enabled = required_extensions.contains(&ash::ext::debug_utils::NAME);
+        let vk_debug_config = Box::new(VkDebugConfig::from_env());
+
         log::debug!("Creating Vulkan instance");
         let vk_instance = {
             let layer_pointers = layers.iter().map(|&s| s.as_ptr()).collect::<Vec<_>>();
@@ -220,11 +423,16 @@ impl XrShell {
                 vk::InstanceCreateFlags::empty()
             };
 
-            let create_info = vk::InstanceCreateInfo::default()
+            let mut create_info = vk::InstanceCreateInfo::default()
                 .flags(flags)
                 .application_info(&app_info)
                 .enabled_layer_names(&layer_pointers)
                 .enabled_extension_names(&required_extensions_ptrs);
+            // Also report problems with creating and destroying the instance itself
+            let mut debug_create_info = vk_debug_config.messenger_create_info();
+            if debug_utils_enabled {
+                create_info = create_info.push_next(&mut debug_create_info);
+            }
 
             unsafe { entry.create_instance(&create_info, None)? }
         };
@@ -239,6 +447,18 @@ impl XrShell {
             0
         };
 
+        let vk_debug_messenger = if debug_utils_enabled {
+            match VkDebugMessenger::new(&entry, &vk_instance, vk_debug_config) {
+                Ok(messenger) => Some(messenger),
+                Err(e) => {
+                    log::warn!("Failed to create Vulkan debug messenger: {e:?}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         log::debug!("Creating Wgpu Hal instance");
         let hal_instance = unsafe {
             <hal::api::Vulkan as hal::Api>::Instance::from_raw(
@@ -246,6 +466,7 @@ impl XrShell {
                 vk_instance.clone(),
                 vk_target_version,
                 android_sdk_version,
+                // Can't be built outside wgpu-hal, we have our own messenger, see crate::vk_debug
                 None, // debug_utils_create_info
                 required_extensions,
                 hal_instance_flags,
@@ -254,7 +475,7 @@ impl XrShell {
             )?
         };
 
-        Ok((vk_instance, hal_instance))
+        Ok((vk_instance, hal_instance, vk_debug_messenger))
     }
 
     /// # Safety
@@ -272,12 +493,14 @@ impl XrShell {
         vk_instance: &ash::Instance,
         vk_target_version: u32,
         features: wgt::Features,
+        optional_features: wgt::Features,
     ) -> (
         vk::PhysicalDevice,
         hal::ExposedAdapter<hal::api::Vulkan>,
         ash::Device,
         hal::OpenDevice<hal::api::Vulkan>,
         u32,
+        wgt::Features,
     ) {
         log::debug!("create_wgpu_hal_device_for_openxr");
 
@@ -289,6 +512,9 @@ impl XrShell {
 
         let hal_adapter = hal_instance.expose_adapter(vk_physical_device).unwrap();
 
+        let features = features | (optional_features & hal_adapter.features);
+        log::debug!("Enabled Wgpu features: {features:?}");
+
         let vk_device_properties = vk_instance.get_physical_device_properties(vk_physical_device);
         if vk_device_properties.api_version < vk_target_version {
             vk_instance.destroy_instance(None);
@@ -393,6 +619,7 @@ impl XrShell {
             vk_device,
             hal_device,
             family_index,
+            features,
         )
     }
 
@@ -401,6 +628,7 @@ impl XrShell {
         system: xr::SystemId,
         session: &xr::Session<xr::vulkan::Vulkan>,
         wgpu_device: &wgpu::Device,
+        vk_object_namer: Option<&VkObjectNamer>,
     ) -> Result<Swapchain> {
         // Now we need to find all the viewpoints we need to take care of! This is a
         // property of the view configuration type; in this example we use PRIMARY_STEREO,
@@ -419,10 +647,35 @@ impl XrShell {
             width: views[0].recommended_image_rect_width,
             height: views[0].recommended_image_rect_height,
         };
+        Self::create_wgpu_swapchain(
+            session,
+            wgpu_device,
+            vk_object_namer,
+            "eyes",
+            resolution,
+            // Each swapchain element is an array-of-two: left eye, right eye
+            2,
+            xr::SwapchainUsageFlags::COLOR_ATTACHMENT | xr::SwapchainUsageFlags::SAMPLED,
+        )
+    }
+
+    /// Create an OpenXR swapchain of [XrShell::COLOR_FORMAT] images with `array_size` layers each,
+    /// and wrap every image in a wgpu texture.
+    ///
+    /// `array_size == 1` images are exposed as D2 views, anything larger as D2Array views over every layer.
+    /// The images are labelled `"{label} swapchain {index}"`.
+    fn create_wgpu_swapchain(
+        session: &xr::Session<xr::vulkan::Vulkan>,
+        wgpu_device: &wgpu::Device,
+        vk_object_namer: Option<&VkObjectNamer>,
+        label: &str,
+        resolution: vk::Extent2D,
+        array_size: u32,
+        usage_flags: xr::SwapchainUsageFlags,
+    ) -> Result<Swapchain> {
         let handle = session.create_swapchain(&xr::SwapchainCreateInfo {
             create_flags: xr::SwapchainCreateFlags::EMPTY,
-            usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT
-                | xr::SwapchainUsageFlags::SAMPLED,
+            usage_flags,
             format: XrShell::COLOR_FORMAT.as_raw() as _,
             // The Vulkan graphics pipeline we create is not set up for multisampling,
             // so we hardcode this to 1. If we used a proper multisampling setup, we
@@ -431,12 +684,24 @@ impl XrShell {
             width: resolution.width,
             height: resolution.height,
             face_count: 1,
-            // Each swapchain element is an array-of-two: left eye, right eye
-            array_size: 2,
+            array_size,
             mip_count: 1,
         })?;
+        xr_debug::set_object_name(
+            session.instance(),
+            xr::sys::ObjectType::SWAPCHAIN,
+            handle.as_raw(),
+            &format!("{label} swapchain"),
+        );
         let swapchain = Arc::new(Mutex::new(handle));
 
+        let mut hal_usage = hal::TextureUses::COLOR_TARGET | hal::TextureUses::RESOURCE;
+        let mut wgpu_usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        if usage_flags.contains(xr::SwapchainUsageFlags::TRANSFER_DST) {
+            hal_usage |= hal::TextureUses::COPY_DST;
+            wgpu_usage |= wgpu::TextureUsages::COPY_DST;
+        }
+
         let hal_texture_desc = hal::TextureDescriptor {
             label: None,
             size: wgpu::Extent3d {
@@ -448,7 +713,7 @@ impl XrShell {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: hal::TextureUses::COLOR_TARGET | hal::TextureUses::RESOURCE,
+            usage: hal_usage,
             memory_flags: hal::MemoryFlags::empty(),
             view_formats: vec![wgpu::TextureFormat::Rgba8UnormSrgb],
         };
@@ -458,17 +723,23 @@ impl XrShell {
             size: wgpu::Extent3d {
                 width: resolution.width,
                 height: resolution.height,
-                // Each "texture" is a swapchain entry - two layers, one per eye
-                depth_or_array_layers: 2,
+                // Each "texture" is a swapchain entry - one layer per array element
+                depth_or_array_layers: array_size,
             },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu_usage,
             view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
         };
 
+        let view_dimension = if array_size > 1 {
+            wgpu::TextureViewDimension::D2Array
+        } else {
+            wgpu::TextureViewDimension::D2
+        };
+
         // We'll want to track our own information about the swapchain, so we can draw stuff
         // onto it! We'll also create a buffer for each generated texture here as well.
         let images = swapchain.lock().unwrap().enumerate_images()?;
@@ -478,8 +749,13 @@ impl XrShell {
                 resolution,
                 buffers: images
                     .into_iter()
-                    .map(|color_image| {
+                    .enumerate()
+                    .map(|(index, color_image)| {
                         let color_image = vk::Image::from_raw(color_image);
+                        let image_label = format!("{label} swapchain {index}");
+                        if let Some(namer) = vk_object_namer {
+                            namer.set_name(color_image, &image_label);
+                        }
 
                         let hal_texture = <hal::api::Vulkan as hal::Api>::Device::texture_from_raw(
                             color_image,
@@ -487,36 +763,57 @@ impl XrShell {
                             Some(Box::new(swapchain.clone())),
                         );
 
-                        let wgpu_texture = wgpu_device.create_texture_from_hal::<hal::api::Vulkan>(
+                        let texture = wgpu_device.create_texture_from_hal::<hal::api::Vulkan>(
                             hal_texture,
-                            &texture_desc,
+                            &wgpu::TextureDescriptor {
+                                label: Some(image_label.as_str()),
+                                ..texture_desc.clone()
+                            },
                         );
 
-                        let color = wgpu_texture.create_view(&wgpu::TextureViewDescriptor {
+                        let color = texture.create_view(&wgpu::TextureViewDescriptor {
                             label: None,
                             format: None,
-                            dimension: Some(wgpu::TextureViewDimension::D2Array),
+                            dimension: Some(view_dimension),
                             aspect: wgpu::TextureAspect::All,
                             base_mip_level: 0,
                             mip_level_count: None,
                             base_array_layer: 0,
-                            // Make the image buffers array-views over both left and right eye
-                            array_layer_count: Some(2),
+                            // Make the image buffers array-views over every layer e.g. both left and right eye
+                            array_layer_count: Some(array_size),
                         });
 
-                        Framebuffer { color }
+                        Framebuffer { texture, color }
                     })
                     .collect(),
             })
         }
     }
 
+    /// Create a single-layer swapchain suitable for a [xr::CompositionLayerQuad],
+    /// which can be rendered to or written directly with [wgpu::Queue::write_texture].
+    pub fn create_quad_swapchain(&self, label: &str, width: u32, height: u32) -> Result<Swapchain> {
+        Self::create_wgpu_swapchain(
+            &self.xr_session,
+            &self.wgpu_device,
+            self.vk_object_namer.as_ref(),
+            label,
+            vk::Extent2D { width, height },
+            1,
+            xr::SwapchainUsageFlags::COLOR_ATTACHMENT
+                | xr::SwapchainUsageFlags::SAMPLED
+                | xr::SwapchainUsageFlags::TRANSFER_DST,
+        )
+    }
+
     pub fn new(
         app_name: &str,
         app_version: u32,
         vk_target_version: u32,
         features: wgt::Features,
+        optional_features: wgt::Features,
         limits: wgt::Limits,
+        pipeline_cache_dir: Option<&Path>,
     ) -> Result<Self> {
         let quit_signal = Arc::new(AtomicBool::new(true));
 
@@ -550,6 +847,21 @@ impl XrShell {
         {
             enabled_extensions.khr_android_create_instance = true;
         }
+        // Optional, used by create_tracking_space() when STAGE isn't available.
+        let xr_local_floor_enabled = available_extensions
+            .other
+            .iter()
+            .any(|ext| ext == Self::LOCAL_FLOOR_EXTENSION_NAME);
+        if xr_local_floor_enabled {
+            enabled_extensions
+                .other
+                .push(Self::LOCAL_FLOOR_EXTENSION_NAME.to_string());
+        }
+
+        // Optional, for runtime messages and object names, see crate::xr_debug
+        enabled_extensions.ext_debug_utils = available_extensions.ext_debug_utils;
+        // Optional, for action set priorities, see crate::action_layers
+        enabled_extensions.ext_active_action_set_priority = available_extensions.ext_active_action_set_priority;
 
         let xr_instance = xr_entry.create_instance(
             &xr::ApplicationInfo {
@@ -562,6 +874,18 @@ impl XrShell {
             &[],
         )?;
 
+        let xr_debug_messenger = if enabled_extensions.ext_debug_utils {
+            match XrDebugMessenger::new(&xr_instance) {
+                Ok(messenger) => Some(messenger),
+                Err(e) => {
+                    log::warn!("Failed to create OpenXR debug messenger: {e:?}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         let instance_props = xr_instance.properties()?;
         log::info!(
             "Loaded OpenXR runtime: {} {}",
@@ -606,7 +930,7 @@ impl XrShell {
         }
 
         unsafe {
-            let (vk_instance, hal_instance) = Self::create_wgpu_hal_instance_for_openxr(
+            let (vk_instance, hal_instance, vk_debug_messenger) = Self::create_wgpu_hal_instance_for_openxr(
                 &xr_instance,
                 xr_system,
                 app_name,
@@ -615,7 +939,8 @@ impl XrShell {
                 Self::hal_instance_flags(),
             )?;
 
-            let (vk_physical_device, hal_adapter, vk_device, hal_device, queue_family_index) =
+            // `features` now also includes whichever `optional_features` the adapter supports
+            let (vk_physical_device, hal_adapter, vk_device, hal_device, queue_family_index, features) =
                 Self::create_wgpu_hal_device_for_openxr(
                     &xr_instance,
                     xr_system,
@@ -623,8 +948,17 @@ impl XrShell {
                     &vk_instance,
                     vk_target_version,
                     features,
+                    optional_features,
                 );
 
+            let vk_object_namer = vk_debug_messenger
+                .is_some()
+                .then(|| VkObjectNamer::new(&vk_instance, &vk_device));
+            if let Some(namer) = &vk_object_namer {
+                namer.set_name(vk_device.handle(), "xr_shell device");
+                namer.set_name(vk_device.get_device_queue(queue_family_index, 0), "xr_shell queue");
+            }
+
             let wgpu_instance = wgpu::Instance::from_hal::<hal::api::Vulkan>(hal_instance);
             let wgpu_adapter = wgpu_instance.create_adapter_from_hal(hal_adapter);
             let (wgpu_device, wgpu_queue) = wgpu_adapter.create_device_from_hal(
@@ -637,6 +971,8 @@ impl XrShell {
                 },
                 None,
             )?;
+            let wgpu_pipeline_cache = pipeline_cache_dir
+                .and_then(|dir| PersistentPipelineCache::load(&wgpu_adapter, &wgpu_device, dir));
 
             // A session represents this application's desire to display things! This is where we hook
             // up our graphics API. This does not start the session; for that, you'll need a call to
@@ -652,9 +988,20 @@ impl XrShell {
                         queue_index: 0,
                     },
                 )?;
+            xr_debug::set_object_name(
+                &xr_instance,
+                xr::sys::ObjectType::SESSION,
+                xr_session.as_raw(),
+                &format!("{app_name} session"),
+            );
 
-            let xr_swapchain =
-                Self::create_swapchain(&xr_instance, xr_system, &xr_session, &wgpu_device)?;
+            let xr_swapchain = Self::create_swapchain(
+                &xr_instance,
+                xr_system,
+                &xr_session,
+                &wgpu_device,
+                vk_object_namer.as_ref(),
+            )?;
 
             let event_storage = xr::EventDataBuffer::new();
             let session_running = false;
@@ -668,6 +1015,10 @@ impl XrShell {
                 wgpu_adapter,
                 wgpu_device,
                 wgpu_queue,
+                wgpu_pipeline_cache,
+                _vk_debug_messenger: vk_debug_messenger,
+                vk_object_namer,
+                _xr_debug_messenger: xr_debug_messenger,
 
                 xr_frame_waiter,
                 xr_frame_stream,
@@ -679,10 +1030,38 @@ impl XrShell {
 
                 quit_signal,
                 session_running,
+                xr_session_state: xr::SessionState::UNKNOWN,
+
+                xr_local_floor_enabled,
+                xr_reference_space_changes: Vec::new(),
+                xr_stage_bounds: None,
+                xr_stage_space: None,
+
+                frame_index: 0,
             })
         }
     }
 
+    /// Name an OpenXR object (e.g. `xr::sys::ObjectType::SPACE` and `space.as_raw()`) so the
+    /// runtime's messages can refer to it. Does nothing without `XR_EXT_debug_utils`.
+    pub fn set_xr_object_name<H: xr::sys::Handle>(&self, object_type: xr::sys::ObjectType, handle: H, name: &str) {
+        xr_debug::set_object_name(&self.xr_instance, object_type, handle, name);
+    }
+
+    /// Pass this as the `cache` of every pipeline, so they're compiled once and reused on later runs.
+    /// `None` when there's no cache directory or the device doesn't support pipeline caches.
+    pub fn pipeline_cache(&self) -> Option<&wgpu::PipelineCache> {
+        self.wgpu_pipeline_cache.as_ref().map(PersistentPipelineCache::cache)
+    }
+
+    /// Write the pipeline cache back to disk, for the next run. Call on a clean exit.
+    pub fn save_pipeline_cache(&self) -> Result<()> {
+        match &self.wgpu_pipeline_cache {
+            Some(cache) => cache.save(),
+            None => Ok(()),
+        }
+    }
+
     pub fn poll_events(&mut self) -> Result<PollStatus> {
         log::info!("Poll Events");
         // Index of the current frame, wrapped by PIPELINE_DEPTH. Not to be confused with the
@@ -700,6 +1079,9 @@ impl XrShell {
         }
 
         let mut status = PollStatus::FRAME;
+        self.xr_reference_space_changes.clear();
+        // Can't query the bounds while the event buffer is borrowed
+        let mut stage_bounds_changed = false;
 
         while let Some(event) = self
             .xr_instance
@@ -712,10 +1094,12 @@ impl XrShell {
                     // Session state change is where we can begin and end sessions, as well as
                     // find quit messages!
                     log::info!("entered state {:?}", e.state());
+                    self.xr_session_state = e.state();
                     match e.state() {
                         xr::SessionState::READY => {
                             self.xr_session.begin(XrShell::VIEW_TYPE).unwrap();
                             self.session_running = true;
+                            stage_bounds_changed = true;
                         }
                         xr::SessionState::STOPPING => {
                             self.xr_session.end().unwrap();
@@ -733,6 +1117,21 @@ impl XrShell {
                     status.set(PollStatus::FRAME, false);
                     status.set(PollStatus::QUIT, true);
                 }
+                ReferenceSpaceChangePending(e) => {
+                    log::info!(
+                        "reference space {:?} will change at {:?}",
+                        e.reference_space_type(),
+                        e.change_time()
+                    );
+                    self.xr_reference_space_changes.push(ReferenceSpaceChange {
+                        space_type: e.reference_space_type(),
+                        change_time: e.change_time(),
+                    });
+                    status.set(PollStatus::REFERENCE_SPACE_CHANGED, true);
+                    if e.reference_space_type() == xr::ReferenceSpaceType::STAGE {
+                        stage_bounds_changed = true;
+                    }
+                }
                 EventsLost(e) => {
                     log::error!("lost {} events", e.lost_event_count());
                 }
@@ -740,6 +1139,10 @@ impl XrShell {
             }
         }
 
+        if stage_bounds_changed {
+            self.refresh_stage_bounds();
+        }
+
         if !self.session_running {
             // Don't grind up the CPU
             std::thread::sleep(Duration::from_millis(100));
@@ -748,4 +1151,213 @@ impl XrShell {
 
         Ok(status)
     }
-}
\ No newline at end of file
+
+    /// Locate the eyes in `space`, patching up invalid poses from `last_valid` (the last [EyeViews::is_fully_valid] views).
+    ///
+    /// - If orientation is invalid the whole of `last_valid` is held.
+    /// - If only position is invalid, the new orientations are combined with the last valid positions.
+    ///
+    /// Either way None is returned if there's nothing to hold, and the frame should be skipped.
+    pub fn locate_eye_views(
+        &self,
+        time: xr::Time,
+        space: &xr::Space,
+        last_valid: Option<&EyeViews>,
+    ) -> Result<Option<EyeViews>> {
+        let (flags, views) = self.xr_session.locate_views(XrShell::VIEW_TYPE, time, space)?;
+        if views.len() != 2 {
+            return Err(anyhow!("Expected 2 views from xrLocateViews, got {}", views.len()));
+        }
+        let mut views = [views[0], views[1]];
+
+        if !flags.contains(xr::ViewStateFlags::ORIENTATION_VALID) {
+            return Ok(last_valid.map(|last_valid| EyeViews {
+                views: last_valid.views,
+                flags,
+                held: true,
+            }));
+        }
+
+        let mut held = false;
+        if !flags.contains(xr::ViewStateFlags::POSITION_VALID) {
+            let Some(last_valid) = last_valid else {
+                return Ok(None);
+            };
+            for (view, last_valid) in views.iter_mut().zip(&last_valid.views) {
+                view.pose.position = last_valid.pose.position;
+            }
+            held = true;
+        }
+
+        Ok(Some(EyeViews { views, flags, held }))
+    }
+
+    /// Which of the [XrShell::FRAMES_IN_FLIGHT] per-frame resources the current frame should use
+    pub fn frame_slot(&self) -> usize {
+        (self.frame_index % Self::FRAMES_IN_FLIGHT as u64) as usize
+    }
+
+    /// The play area in STAGE space, if the runtime knows it.
+    /// Refreshed when the session starts and whenever the STAGE space changes.
+    pub fn stage_bounds(&self) -> Option<StageBounds> {
+        self.xr_stage_bounds
+    }
+
+    /// Without STAGE support, or if the runtime can't say, there are just no bounds.
+    /// Runtimes without a play area are what the LOCAL_FLOOR and LOCAL tracking spaces are for.
+    pub fn refresh_stage_bounds(&mut self) {
+        self.xr_stage_bounds = match self.query_stage_bounds() {
+            Ok(bounds) => bounds,
+            Err(e) => {
+                log::warn!("Couldn't get the stage bounds: {e:#}");
+                None
+            }
+        };
+        log::info!("Stage bounds: {:?}", self.xr_stage_bounds);
+
+        // The space keeps following STAGE when its origin changes, so it only needs creating once
+        if self.xr_stage_bounds.is_some() && self.xr_stage_space.is_none() {
+            self.xr_stage_space = match self
+                .xr_session
+                .create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)
+            {
+                Ok(space) => {
+                    self.set_xr_object_name(xr::sys::ObjectType::SPACE, space.as_raw(), "stage bounds space");
+                    Some(space)
+                }
+                Err(e) => {
+                    log::warn!("Couldn't create the STAGE space: {e:#}");
+                    None
+                }
+            };
+        }
+    }
+
+    /// The [XrShell::stage_bounds], and the transform from STAGE space into `space` to place them with.
+    /// None if there are no bounds, or STAGE can't be located in `space` right now.
+    pub fn locate_stage_bounds(&self, space: &xr::Space, time: xr::Time) -> Result<Option<(StageBounds, Mat4)>> {
+        let (Some(bounds), Some(stage_space)) = (self.stage_bounds(), &self.xr_stage_space) else {
+            return Ok(None);
+        };
+        let stage = stage_space.locate(space, time)?;
+        let valid = xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID;
+        if !stage.location_flags.contains(valid) {
+            return Ok(None);
+        }
+        Ok(Some((bounds, Pose::from(stage.pose).posed_from_local())))
+    }
+
+    fn query_stage_bounds(&self) -> Result<Option<StageBounds>> {
+        if !self
+            .xr_session
+            .enumerate_reference_spaces()?
+            .contains(&xr::ReferenceSpaceType::STAGE)
+        {
+            return Ok(None);
+        }
+        Ok(self
+            .xr_session
+            .reference_space_bounds_rect(xr::ReferenceSpaceType::STAGE)?
+            .map(|extent| StageBounds {
+                width: extent.width,
+                depth: extent.height,
+            }))
+    }
+
+    /// Create the first reference space in `preferences` supported by the runtime.
+    /// LOCAL_FLOOR is only considered if XR_EXT_local_floor was enabled.
+    pub fn create_tracking_space(&self, preferences: &[TrackingSpaceKind]) -> Result<TrackingSpace> {
+        let supported = self.xr_session.enumerate_reference_spaces()?;
+        log::info!("Supported reference spaces: {:?}", supported);
+
+        for &kind in preferences {
+            let (space_type, base_pose) = match kind {
+                TrackingSpaceKind::Stage => (xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY),
+                TrackingSpaceKind::LocalFloor => {
+                    if !self.xr_local_floor_enabled {
+                        continue;
+                    }
+                    (Self::local_floor_reference_space_type(), xr::Posef::IDENTITY)
+                }
+                TrackingSpaceKind::LocalWithEstimatedFloor { eye_height } => (
+                    xr::ReferenceSpaceType::LOCAL,
+                    xr::Posef {
+                        orientation: xr::Posef::IDENTITY.orientation,
+                        position: xr::Vector3f {
+                            x: 0.0,
+                            y: -eye_height,
+                            z: 0.0,
+                        },
+                    },
+                ),
+            };
+            if !supported.contains(&space_type) {
+                continue;
+            }
+
+            let space = self.xr_session.create_reference_space(space_type, base_pose)?;
+            self.set_xr_object_name(xr::sys::ObjectType::SPACE, space.as_raw(), &format!("{kind:?} tracking space"));
+            log::info!("Using {:?} tracking space", kind);
+            return Ok(TrackingSpace {
+                space,
+                kind,
+                base_pose,
+                pending_reset: None,
+            });
+        }
+
+        Err(anyhow!(
+            "None of the requested tracking spaces {:?} are supported, runtime supports {:?}",
+            preferences,
+            supported
+        ))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: StageBounds = StageBounds { width: 4.0, depth: 3.0 };
+
+    #[test]
+    fn corners_are_at_half_extents_on_the_floor() {
+        for [x, y, z] in BOUNDS.corners() {
+            assert_eq!(x.abs(), 2.0);
+            assert_eq!(y, 0.0);
+            assert_eq!(z.abs(), 1.5);
+        }
+    }
+
+    #[test]
+    fn corners_are_counter_clockwise_from_above() {
+        let corners = BOUNDS.corners();
+        for i in 0..4 {
+            let [a, b, c] = [corners[i], corners[(i + 1) % 4], corners[(i + 2) % 4]];
+            let (ab, bc) = ([b[0] - a[0], b[2] - a[2]], [c[0] - b[0], c[2] - b[2]]);
+            // Y component of ab x bc, which points up for a counter-clockwise turn
+            assert!(ab[1] * bc[0] - ab[0] * bc[1] > 0.0, "turn at corner {}", i + 1);
+        }
+    }
+
+    #[test]
+    fn contains_corners_and_center() {
+        assert!(BOUNDS.contains([0.0, 0.0, 0.0]));
+        for corner in BOUNDS.corners() {
+            assert!(BOUNDS.contains(corner));
+        }
+    }
+
+    #[test]
+    fn contains_ignores_height() {
+        assert!(BOUNDS.contains([1.0, 2.0, -1.0]));
+        assert!(BOUNDS.contains([1.0, -2.0, -1.0]));
+    }
+
+    #[test]
+    fn outside_either_extent_is_not_contained() {
+        assert!(!BOUNDS.contains([2.01, 0.0, 0.0]));
+        assert!(!BOUNDS.contains([-2.01, 0.0, 0.0]));
+        assert!(!BOUNDS.contains([0.0, 0.0, 1.51]));
+        assert!(!BOUNDS.contains([0.0, 0.0, -1.51]));
+    }
+}
@@ -0,0 +1,464 @@
+//! Loading images into sampled textures.
+//!
+//! [TextureLoader] decodes PNG and JPEG (via the `image` crate) and KTX2 files into [Texture]s,
+//! generating the mip chain on the GPU when the file doesn't provide one. It also hands out
+//! shared [wgpu::Sampler]s and builds [TextureMaterial] bind groups, which pair a texture with a
+//! sampler using the layout from [TextureLoader::material_layout].
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{shader::include_shader, shader_bindings::material, shell::XrShell};
+
+/// Number of levels in a full mip chain down to 1x1
+pub fn full_mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// A sampled 2D texture
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+impl Texture {
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+    /// Width / height
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width() as f32 / self.height() as f32
+    }
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.texture.format()
+    }
+}
+
+/// The parts of a [wgpu::SamplerDescriptor] which samplers are shared by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    address_mode_u: wgpu::AddressMode,
+    address_mode_v: wgpu::AddressMode,
+    mag_filter: wgpu::FilterMode,
+    min_filter: wgpu::FilterMode,
+    mipmap_filter: wgpu::FilterMode,
+}
+impl From<&wgpu::SamplerDescriptor<'_>> for SamplerKey {
+    fn from(desc: &wgpu::SamplerDescriptor<'_>) -> Self {
+        Self {
+            address_mode_u: desc.address_mode_u,
+            address_mode_v: desc.address_mode_v,
+            mag_filter: desc.mag_filter,
+            min_filter: desc.min_filter,
+            mipmap_filter: desc.mipmap_filter,
+        }
+    }
+}
+
+/// Trilinear filtering, clamped at the edges. Used for images and the mip chain generation.
+pub const LINEAR_CLAMP: wgpu::SamplerDescriptor<'static> = wgpu::SamplerDescriptor {
+    label: Some("linear_clamp"),
+    address_mode_u: wgpu::AddressMode::ClampToEdge,
+    address_mode_v: wgpu::AddressMode::ClampToEdge,
+    address_mode_w: wgpu::AddressMode::ClampToEdge,
+    mag_filter: wgpu::FilterMode::Linear,
+    min_filter: wgpu::FilterMode::Linear,
+    mipmap_filter: wgpu::FilterMode::Linear,
+    lod_min_clamp: 0.0,
+    lod_max_clamp: 32.0,
+    compare: None,
+    anisotropy_clamp: 1,
+    border_color: None,
+};
+
+/// Renders each mip level of a texture from the one above it
+struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Created on first use for each format
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+impl MipmapGenerator {
+    fn new(xr_shell: &XrShell) -> Result<Self> {
+        let shader = include_shader!(xr_shell, "shaders/wgsl/mipmap.wgsl")?;
+        let bind_group_layout = xr_shell.wgpu_device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("mipmap"),
+            entries: &TextureLoader::material_layout_entries(),
+        });
+        let pipeline_layout = xr_shell.wgpu_device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("mipmap"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        Ok(Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+        })
+    }
+
+    fn pipeline(&mut self, xr_shell: &XrShell, format: wgpu::TextureFormat) -> &wgpu::RenderPipeline {
+        self.pipelines.entry(format).or_insert_with(|| {
+            xr_shell
+                .wgpu_device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    cache: xr_shell.pipeline_cache(),
+                    label: Some("mipmap"),
+                    layout: Some(&self.pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &self.shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &self.shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(format.into())],
+                        compilation_options: Default::default(),
+                    }),
+                    multiview: None,
+                })
+        })
+    }
+
+    /// Fill levels 1.. of `texture` from level 0.
+    /// The texture must have been created with `RENDER_ATTACHMENT | TEXTURE_BINDING` usage.
+    fn generate(&mut self, xr_shell: &XrShell, sampler: &wgpu::Sampler, texture: &wgpu::Texture) {
+        if texture.mip_level_count() < 2 {
+            return;
+        }
+
+        let format = texture.format();
+        // Create the pipeline before borrowing the layout for the bind groups
+        self.pipeline(xr_shell, format);
+        let pipeline = &self.pipelines[&format];
+
+        let views: Vec<wgpu::TextureView> = (0..texture.mip_level_count())
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mip"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let mut encoder = xr_shell
+            .wgpu_device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("mipmap") });
+        for level in views.windows(2) {
+            let bind_group = xr_shell.wgpu_device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("mipmap"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: material::material_texture::BINDING,
+                        resource: wgpu::BindingResource::TextureView(&level[0]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: material::material_sampler::BINDING,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            });
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("mipmap"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &level[1],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        xr_shell.wgpu_queue.submit(Some(encoder.finish()));
+    }
+}
+
+/// A texture and sampler bound together with [TextureLoader::material_layout]
+pub struct TextureMaterial {
+    pub texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+/// Loads textures, and owns the resources shared between them
+pub struct TextureLoader {
+    mipmaps: MipmapGenerator,
+    samplers: HashMap<SamplerKey, wgpu::Sampler>,
+    material_layout: wgpu::BindGroupLayout,
+}
+
+impl TextureLoader {
+    pub fn new(xr_shell: &XrShell) -> Result<Self> {
+        let material_layout = xr_shell.wgpu_device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("material"),
+            entries: &Self::material_layout_entries(),
+        });
+        Ok(Self {
+            mipmaps: MipmapGenerator::new(xr_shell)?,
+            samplers: HashMap::new(),
+            material_layout,
+        })
+    }
+
+    /// A filterable 2D texture and its sampler, visible to the fragment stage.
+    /// Reflected from `textured_quad.wgsl`, `mesh.wgsl` and `mipmap.wgsl`, see [crate::shader_bindings].
+    pub fn material_layout_entries() -> [wgpu::BindGroupLayoutEntry; 2] {
+        material::LAYOUT_ENTRIES
+    }
+
+    /// The layout of every [TextureMaterial]'s bind group
+    pub fn material_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.material_layout
+    }
+
+    /// Get a sampler matching `desc`, creating it the first time it's asked for
+    pub fn sampler(&mut self, xr_shell: &XrShell, desc: &wgpu::SamplerDescriptor) -> &wgpu::Sampler {
+        self.samplers
+            .entry(SamplerKey::from(desc))
+            .or_insert_with(|| xr_shell.wgpu_device.create_sampler(desc))
+    }
+
+    /// Bind a texture with a (shared) sampler
+    pub fn create_material(
+        &mut self,
+        xr_shell: &XrShell,
+        texture: Texture,
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> TextureMaterial {
+        let bind_group = self.bind(xr_shell, &texture, sampler);
+        TextureMaterial { texture, bind_group }
+    }
+
+    /// Bind a texture owned elsewhere with a (shared) sampler, for textures used by more than one material
+    pub fn bind(
+        &mut self,
+        xr_shell: &XrShell,
+        texture: &Texture,
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> wgpu::BindGroup {
+        self.sampler(xr_shell, sampler);
+        let sampler = &self.samplers[&SamplerKey::from(sampler)];
+        xr_shell.wgpu_device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("material"),
+            layout: &self.material_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: material::material_texture::BINDING,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: material::material_sampler::BINDING,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    /// Load a PNG, JPEG or KTX2 file, chosen by its extension
+    pub fn load(&mut self, xr_shell: &XrShell, path: impl AsRef<Path>, srgb: bool) -> Result<Texture> {
+        let path = path.as_ref();
+        let label = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        let bytes = std::fs::read(path).with_context(|| format!("Reading {}", path.display()))?;
+        let is_ktx2 = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ktx2"));
+        if is_ktx2 {
+            self.from_ktx2(xr_shell, &bytes, label.as_deref())
+        } else {
+            let image = image::load_from_memory(&bytes).with_context(|| format!("Decoding {}", path.display()))?;
+            Ok(self.from_image(xr_shell, &image, srgb, label.as_deref()))
+        }
+    }
+
+    /// Upload a decoded image, with a full mip chain
+    pub fn from_image(
+        &mut self,
+        xr_shell: &XrShell,
+        image: &image::DynamicImage,
+        srgb: bool,
+        label: Option<&str>,
+    ) -> Texture {
+        let rgba8 = image.to_rgba8();
+        self.from_rgba8(xr_shell, rgba8.width(), rgba8.height(), &rgba8, srgb, label)
+    }
+
+    /// Upload tightly packed RGBA8 texels, with a full mip chain
+    pub fn from_rgba8(
+        &mut self,
+        xr_shell: &XrShell,
+        width: u32,
+        height: u32,
+        rgba8: &[u8],
+        srgb: bool,
+        label: Option<&str>,
+    ) -> Texture {
+        debug_assert_eq!(rgba8.len(), (width * height * 4) as usize);
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let texture = xr_shell.wgpu_device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: full_mip_level_count(width, height),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        xr_shell.wgpu_queue.write_texture(
+            texture.as_image_copy(),
+            rgba8,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: None,
+            },
+            texture.size(),
+        );
+
+        // The mip chain is rendered in its own submission, which comes after the upload above
+        self.sampler(xr_shell, &LINEAR_CLAMP);
+        let sampler = &self.samplers[&SamplerKey::from(&LINEAR_CLAMP)];
+        self.mipmaps.generate(xr_shell, sampler, &texture);
+
+        let view = texture.create_view(&Default::default());
+        Texture { texture, view }
+    }
+
+    /// Upload a KTX2 file without supercompression. Uncompressed RGBA8 files which only contain
+    /// the base level get a generated mip chain; otherwise the levels in the file are used as-is.
+    pub fn from_ktx2(&mut self, xr_shell: &XrShell, bytes: &[u8], label: Option<&str>) -> Result<Texture> {
+        let reader = ktx2::Reader::new(bytes).map_err(|e| anyhow!("Parsing KTX2: {e}"))?;
+        let header = reader.header();
+
+        if let Some(scheme) = header.supercompression_scheme {
+            anyhow::bail!("KTX2 supercompression {scheme:?} is not supported");
+        }
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            anyhow::bail!("Only single-layer 2D KTX2 textures are supported");
+        }
+        let ktx2_format = header.format.context("KTX2 without a Vulkan format is not supported")?;
+        let format = ktx2_format_to_wgpu(ktx2_format)
+            .with_context(|| format!("KTX2 format {ktx2_format:?} is not supported"))?;
+        let missing_features = format.required_features().difference(xr_shell.wgpu_device.features());
+        if !missing_features.is_empty() {
+            anyhow::bail!("KTX2 format {format:?} requires device features {missing_features:?}");
+        }
+
+        let width = header.pixel_width;
+        let height = header.pixel_height.max(1);
+        let levels: Vec<&[u8]> = reader.levels().collect();
+        let generate_mips = levels.len() == 1 && !format.is_compressed();
+        let mip_level_count = if generate_mips {
+            full_mip_level_count(width, height)
+        } else {
+            levels.len() as u32
+        };
+
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if generate_mips {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        let texture = xr_shell.wgpu_device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format
+            .block_copy_size(None)
+            .context("KTX2 format has no single copy size")?;
+        for (level, data) in levels.iter().enumerate() {
+            let level_size = texture.size().mip_level_size(level as u32, wgpu::TextureDimension::D2);
+            let blocks_wide = level_size.width.div_ceil(block_width);
+            let blocks_high = level_size.height.div_ceil(block_height);
+            let expected_len = (blocks_wide * blocks_high * block_size) as usize;
+            if data.len() < expected_len {
+                anyhow::bail!("KTX2 level {level} has {} bytes, expected {expected_len}", data.len());
+            }
+            xr_shell.wgpu_queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &data[..expected_len],
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(blocks_wide * block_size),
+                    rows_per_image: None,
+                },
+                level_size.physical_size(format),
+            );
+        }
+
+        if generate_mips {
+            self.sampler(xr_shell, &LINEAR_CLAMP);
+            let sampler = &self.samplers[&SamplerKey::from(&LINEAR_CLAMP)];
+            self.mipmaps.generate(xr_shell, sampler, &texture);
+        }
+
+        let view = texture.create_view(&Default::default());
+        Ok(Texture { texture, view })
+    }
+}
+
+fn ktx2_format_to_wgpu(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat};
+    Some(match format {
+        ktx2::Format::R8G8B8A8_UNORM => TextureFormat::Rgba8Unorm,
+        ktx2::Format::R8G8B8A8_SRGB => TextureFormat::Rgba8UnormSrgb,
+        ktx2::Format::BC7_UNORM_BLOCK => TextureFormat::Bc7RgbaUnorm,
+        ktx2::Format::BC7_SRGB_BLOCK => TextureFormat::Bc7RgbaUnormSrgb,
+        ktx2::Format::ETC2_R8G8B8A8_UNORM_BLOCK => TextureFormat::Etc2Rgba8Unorm,
+        ktx2::Format::ETC2_R8G8B8A8_SRGB_BLOCK => TextureFormat::Etc2Rgba8UnormSrgb,
+        ktx2::Format::ASTC_4x4_UNORM_BLOCK => TextureFormat::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::Unorm,
+        },
+        ktx2::Format::ASTC_4x4_SRGB_BLOCK => TextureFormat::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::UnormSrgb,
+        },
+        _ => return None,
+    })
+}
@@ -0,0 +1,149 @@
+//! Vulkan `VK_EXT_debug_utils`: validation layer messages routed into [log], and names for the
+//! Vulkan objects the shell creates so they can be told apart in those messages and in RenderDoc.
+//!
+//! wgpu-hal's own messenger can't be used, because its `DebugUtilsCreateInfo` can only be built
+//! inside wgpu-hal, so `Instance::from_raw` is still given `None` and we create the messenger here.
+//! The extension is only used when wgpu-hal asked for it (with [wgpu::InstanceFlags::DEBUG]), which
+//! it doesn't on the Quest, see [crate::shell::XrShell].
+
+use std::ffi::{c_void, CStr, CString};
+
+use anyhow::Result;
+use ash::vk;
+
+/// What the messenger callback does with messages
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VkDebugConfig {
+    /// Panic on error messages. The panic can't unwind out of the `extern "system"` callback, so this
+    /// aborts the whole process on the spot, without running destructors or flushing anything but the log line.
+    /// For CI runs, where an error should fail the run rather than scroll past.
+    pub panic_on_error: bool,
+}
+
+impl VkDebugConfig {
+    /// Set `VK_PANIC_ON_ERROR` to abort on the first error, see [VkDebugConfig::panic_on_error]
+    pub const PANIC_ON_ERROR_VAR: &'static str = "VK_PANIC_ON_ERROR";
+
+    pub fn from_env() -> Self {
+        Self {
+            panic_on_error: std::env::var_os(Self::PANIC_ON_ERROR_VAR).is_some(),
+        }
+    }
+
+    /// Messenger create info sending every message to [debug_utils_callback] with `self` as its user data.
+    /// Also usable with `push_next` on [vk::InstanceCreateInfo], to catch messages from instance creation.
+    pub fn messenger_create_info(&self) -> vk::DebugUtilsMessengerCreateInfoEXT<'_> {
+        vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(
+                vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            )
+            .message_type(
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            )
+            .pfn_user_callback(Some(debug_utils_callback))
+            .user_data(self as *const Self as *mut c_void)
+    }
+}
+
+unsafe extern "system" fn debug_utils_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    p_user_data: *mut c_void,
+) -> vk::Bool32 {
+    let level = if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        log::Level::Error
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        log::Level::Warn
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        // The loader and layers are chatty at INFO, keep it out of the app's own info logs
+        log::Level::Debug
+    } else {
+        log::Level::Trace
+    };
+
+    let (id_name, message) = match p_callback_data.as_ref() {
+        Some(data) => (
+            data.message_id_name_as_c_str().map(CStr::to_string_lossy),
+            data.message_as_c_str().map(CStr::to_string_lossy),
+        ),
+        None => (None, None),
+    };
+    log::log!(
+        target: "vulkan",
+        level,
+        "{message_types:?} [{}] {}",
+        id_name.as_deref().unwrap_or("-"),
+        message.as_deref().unwrap_or("(no message)"),
+    );
+
+    let config = (p_user_data as *const VkDebugConfig).as_ref();
+    if level == log::Level::Error && config.is_some_and(|config| config.panic_on_error) {
+        // Aborts, as unwinding stops at the extern "system" boundary
+        panic!("Vulkan validation error, see the log above");
+    }
+
+    // Don't abort the call that triggered the message
+    vk::FALSE
+}
+
+/// A debug-utils messenger, destroyed when dropped
+pub struct VkDebugMessenger {
+    loader: ash::ext::debug_utils::Instance,
+    messenger: vk::DebugUtilsMessengerEXT,
+    // The callback's user data, which must stay at the same address while the messenger exists
+    _config: Box<VkDebugConfig>,
+}
+
+impl VkDebugMessenger {
+    /// The instance must have been created with [ash::ext::debug_utils::NAME] enabled
+    pub fn new(entry: &ash::Entry, instance: &ash::Instance, config: Box<VkDebugConfig>) -> Result<Self> {
+        let loader = ash::ext::debug_utils::Instance::new(entry, instance);
+        let messenger = unsafe { loader.create_debug_utils_messenger(&config.messenger_create_info(), None)? };
+        Ok(Self {
+            loader,
+            messenger,
+            _config: config,
+        })
+    }
+}
+
+impl Drop for VkDebugMessenger {
+    fn drop(&mut self) {
+        unsafe {
+            self.loader.destroy_debug_utils_messenger(self.messenger, None);
+        }
+    }
+}
+
+/// Names Vulkan objects with `vkSetDebugUtilsObjectNameEXT`
+pub struct VkObjectNamer {
+    device: ash::ext::debug_utils::Device,
+}
+
+impl VkObjectNamer {
+    pub fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        Self {
+            device: ash::ext::debug_utils::Device::new(instance, device),
+        }
+    }
+
+    /// Failures are only logged, a missing name isn't worth stopping for
+    pub fn set_name<T: vk::Handle>(&self, handle: T, name: &str) {
+        let Ok(name) = CString::new(name) else {
+            log::warn!("Vulkan object name {name:?} contains a nul byte");
+            return;
+        };
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(handle)
+            .object_name(&name);
+        if let Err(e) = unsafe { self.device.set_debug_utils_object_name(&name_info) } {
+            log::warn!("Failed to name Vulkan object {name:?}: {e}");
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! OpenXR `XR_EXT_debug_utils`: runtime messages routed into [log], and names for the OpenXR objects
+//! we create so the runtime's messages say which session, space, swapchain or action set they mean.
+//!
+//! Both are optional; without the extension nothing is logged and naming does nothing.
+
+use std::ffi::{c_char, c_void, CStr};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::xr;
+use xr::sys::Handle as _;
+
+/// A debug-utils messenger, destroyed when dropped
+pub struct XrDebugMessenger {
+    // Keeps the instance alive until the messenger is destroyed
+    instance: xr::Instance,
+    messenger: xr::sys::DebugUtilsMessengerEXT,
+}
+
+impl XrDebugMessenger {
+    /// The instance must have been created with `ext_debug_utils` enabled
+    pub fn new(instance: &xr::Instance) -> Result<Self> {
+        let debug_utils = instance
+            .exts()
+            .ext_debug_utils
+            .as_ref()
+            .context("XR_EXT_debug_utils is not enabled")?;
+
+        let create_info = xr::sys::DebugUtilsMessengerCreateInfoEXT {
+            ty: xr::sys::DebugUtilsMessengerCreateInfoEXT::TYPE,
+            next: std::ptr::null(),
+            message_severities: xr::sys::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                | xr::sys::DebugUtilsMessageSeverityFlagsEXT::INFO
+                | xr::sys::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | xr::sys::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            message_types: xr::sys::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | xr::sys::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | xr::sys::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+                | xr::sys::DebugUtilsMessageTypeFlagsEXT::CONFORMANCE,
+            user_callback: Some(debug_utils_callback),
+            user_data: std::ptr::null_mut(),
+        };
+        let mut messenger = xr::sys::DebugUtilsMessengerEXT::NULL;
+        let result = unsafe {
+            (debug_utils.create_debug_utils_messenger)(instance.as_raw(), &create_info, &mut messenger)
+        };
+        if result != xr::sys::Result::SUCCESS {
+            return Err(anyhow!("xrCreateDebugUtilsMessengerEXT failed: {result:?}"));
+        }
+        Ok(Self {
+            instance: instance.clone(),
+            messenger,
+        })
+    }
+}
+
+impl Drop for XrDebugMessenger {
+    fn drop(&mut self) {
+        if let Some(debug_utils) = self.instance.exts().ext_debug_utils.as_ref() {
+            let _ = unsafe { (debug_utils.destroy_debug_utils_messenger)(self.messenger) };
+        }
+    }
+}
+
+/// Read a nullable C string from the runtime
+unsafe fn runtime_str<'a>(ptr: *const c_char) -> Option<std::borrow::Cow<'a, str>> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy())
+}
+
+unsafe extern "system" fn debug_utils_callback(
+    message_severity: xr::sys::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: xr::sys::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const xr::sys::DebugUtilsMessengerCallbackDataEXT,
+    _user_data: *mut c_void,
+) -> xr::sys::Bool32 {
+    let level = if message_severity.contains(xr::sys::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        log::Level::Error
+    } else if message_severity.contains(xr::sys::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        log::Level::Warn
+    } else if message_severity.contains(xr::sys::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        log::Level::Debug
+    } else {
+        log::Level::Trace
+    };
+
+    let (message_id, function_name, message) = match callback_data.as_ref() {
+        Some(data) => (
+            runtime_str(data.message_id),
+            runtime_str(data.function_name),
+            runtime_str(data.message),
+        ),
+        None => (None, None, None),
+    };
+    log::log!(
+        target: "openxr",
+        level,
+        "{message_types:?} [{}] {}: {}",
+        message_id.as_deref().unwrap_or("-"),
+        function_name.as_deref().unwrap_or("-"),
+        message.as_deref().unwrap_or("(no message)"),
+    );
+
+    // Don't fail the call that triggered the message
+    xr::sys::FALSE
+}
+
+/// Name an OpenXR object for the runtime's messages. Does nothing without `XR_EXT_debug_utils`,
+/// and failures are only logged.
+pub fn set_object_name<H: xr::sys::Handle>(
+    instance: &xr::Instance,
+    object_type: xr::sys::ObjectType,
+    handle: H,
+    name: &str,
+) {
+    let Some(debug_utils) = instance.exts().ext_debug_utils.as_ref() else {
+        return;
+    };
+    let Ok(c_name) = std::ffi::CString::new(name) else {
+        log::warn!("OpenXR object name {name:?} contains a nul byte");
+        return;
+    };
+    let name_info = xr::sys::DebugUtilsObjectNameInfoEXT {
+        ty: xr::sys::DebugUtilsObjectNameInfoEXT::TYPE,
+        next: std::ptr::null(),
+        object_type,
+        object_handle: handle.into_raw(),
+        object_name: c_name.as_ptr(),
+    };
+    let result = unsafe { (debug_utils.set_debug_utils_object_name)(instance.as_raw(), &name_info) };
+    if result != xr::sys::Result::SUCCESS {
+        log::warn!("Failed to name OpenXR object {name:?}: {result:?}");
+    }
+}